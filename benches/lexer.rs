@@ -0,0 +1,116 @@
+// 数MBの入力で字句解析と構文解析の速さを測る．入力の長さに比例した時間になるはず
+//
+// cargo bench --bench lexer
+
+use std::time::{Duration, Instant};
+
+use ruly2::*;
+
+syntax!(
+    WHITESPACE {
+        r"[ \n\r\t]*"
+    }
+
+    TOKEN {
+        Let => {"let", Reserved}
+        Print => {"print", Reserved}
+        Str => {r#""[^"]*""#}
+        Id => {"[a-z_][a-z0-9_]*"}
+        N => {"[0-9]+", u64}
+        Plus => {r"\+"}
+        Star => {r"\*"}
+        LP => {r"\("}
+        RP => {r"\)"}
+        Eq => {"="}
+        Semi => {";"}
+    }
+
+    RULE {
+        Prog =>
+            | Stmts(Vec<Stmt>)
+
+        Stmt =>
+            | Let(Let, Id, Eq, E, Semi)
+            | Print(Print, E, Semi)
+
+        E =>
+            | Add(E, Plus, T)
+            | T(T)
+
+        T =>
+            | Mul(T, Star, F)
+            | F(F)
+
+        F =>
+            | Id(Id)
+            | N(N)
+            | Str(Str)
+            | Paren(LP, E, RP)
+    }
+
+    START {
+        Prog
+    }
+
+    ALGORITHM {
+        LALR
+    }
+);
+
+const MIB: usize = 1 << 20;
+
+// 入力と，その字句の数と文の数
+fn generate(size: usize) -> (String, usize, usize) {
+    let mut ret = String::with_capacity(size + 64);
+
+    let mut i = 0;
+    while ret.len() < size {
+        ret += &format!("let x_{0} = (a + {0}) * b_3 + 7;\nprint x_{0} * 2;\n", i);
+        i += 1;
+    }
+    // 文字列は末尾にしかないので，字句ごとに先読みすると毎回末尾まで探すことになる
+    ret += "print \"done\";\n";
+
+    (ret, 18 * i + 3, 2 * i + 1)
+}
+
+// 3回測って最も速いもの
+fn measure(f: impl Fn()) -> Duration {
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn report(name: &str, s: &str, elapsed: Duration) {
+    let mib = s.len() as f64 / MIB as f64;
+    println!(
+        "{:<8} {:>5.1} MiB: {:>9.1} ms ({:>6.1} MiB/s)",
+        name,
+        mib,
+        elapsed.as_secs_f64() * 1000.0,
+        mib / elapsed.as_secs_f64()
+    );
+}
+
+fn main() {
+    for size in [MIB, 2 * MIB, 4 * MIB, 8 * MIB] {
+        let (s, tokens, statements) = generate(size);
+
+        let elapsed = measure(|| {
+            let v = Lex::tokenize(&s).unwrap();
+            assert_eq!(v.len(), tokens);
+        });
+        report("tokenize", &s, elapsed);
+
+        let elapsed = measure(|| {
+            let Prog::Stmts(stmts) = Parser::parse(&s).unwrap();
+            assert_eq!(stmts.len(), statements);
+        });
+        report("parse", &s, elapsed);
+    }
+}
@@ -0,0 +1,80 @@
+use proc_macro::{Group, Punct, Spacing, TokenStream, TokenTree, TokenTree::*};
+use std::collections::HashMap;
+
+// Map<規則番号, 意味動作>．利用者の書いた位置を保ったまま生成したコードに埋め込む
+pub type Actions = HashMap<usize, TokenStream>;
+
+// 意味動作の$Nを，右辺のN番目の値を表すvalue(N)に置き換える
+pub fn replace_fields(stream: TokenStream, value: &dyn Fn(usize) -> TokenStream) -> TokenStream {
+    let mut ret = vec![];
+    let mut it = stream.into_iter();
+
+    while let Some(tt) = it.next() {
+        match tt {
+            Punct(p) if p.as_char() == '$' => {
+                let tt = it.next();
+                // $0.0は$と0.0に分かれるので，.より前を番号，後ろを欄の参照とみなす
+                let lit = match &tt {
+                    Some(Literal(lit)) => lit.to_string(),
+                    _ => String::new(),
+                };
+                let (n, rest) = lit.split_once('.').unwrap_or((&lit, ""));
+
+                if let Ok(n) = n.parse() {
+                    ret.extend(value(n));
+                    if !rest.is_empty() {
+                        ret.push(TokenTree::from(Punct::new('.', Spacing::Alone)));
+                        ret.extend(rest.parse::<TokenStream>().unwrap());
+                    }
+                } else {
+                    panic!(
+                        "$ must be followed by the index of a field! (${})",
+                        tt.map(|x| x.to_string()).unwrap_or_default()
+                    );
+                }
+            }
+
+            Group(grp) => {
+                let mut new_grp = Group::new(grp.delimiter(), replace_fields(grp.stream(), value));
+                new_grp.set_span(grp.span());
+                ret.push(TokenTree::from(new_grp));
+            }
+
+            tt => ret.push(tt),
+        }
+    }
+
+    ret.into_iter().collect()
+}
+
+// 生成したコードの文字列の中で，規則番号nの意味動作に置き換わる目印
+pub fn placeholder(n: usize) -> String {
+    format!("__ruly_action_{}", n)
+}
+
+// 目印を意味動作に置き換える．文字列を経由すると，意味動作の誤りが呼び出し元を指してしまう
+pub fn splice(stream: TokenStream, actions: &Actions) -> TokenStream {
+    stream
+        .into_iter()
+        .flat_map(|tt| match tt {
+            Ident(ident) => {
+                let s = ident.to_string();
+                match s
+                    .strip_prefix("__ruly_action_")
+                    .and_then(|x| x.parse().ok())
+                {
+                    Some(n) => actions[&n].clone(),
+                    None => TokenStream::from(TokenTree::from(ident)),
+                }
+            }
+
+            Group(grp) => {
+                let mut new_grp = Group::new(grp.delimiter(), splice(grp.stream(), actions));
+                new_grp.set_span(grp.span());
+                TokenStream::from(TokenTree::from(new_grp))
+            }
+
+            tt => TokenStream::from(tt),
+        })
+        .collect()
+}
@@ -11,3 +11,23 @@ pub fn get_ident_string(arg: Option<TokenTree>) -> String {
         panic!()
     }
 }
+
+// 文法の誤りをcompile_error!にせず，実行時のErrにするか
+pub fn get_lazy_flag(arg: Option<TokenTree>) -> bool {
+    if let Some(Group(grp)) = arg {
+        let mut it = grp.stream().into_iter();
+
+        match it.next() {
+            None => false,
+            Some(tt) => {
+                let s = get_ident_string(Some(tt));
+                if s != "Lazy" || it.next().is_some() {
+                    panic!("The only option of ALGORITHM is Lazy! ({})", s);
+                }
+                true
+            }
+        }
+    } else {
+        panic!()
+    }
+}
@@ -0,0 +1,116 @@
+use proc_macro::{Delimiter, TokenTree, TokenTree::*};
+
+use crate::common::get_ident_string;
+use crate::item::Item;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    ShiftReduce,
+    ReduceReduce,
+}
+
+// 構文解析表の1つの欄を取り合う2つの動作
+#[derive(Debug)]
+pub struct Conflict {
+    pub state: usize,
+    pub lookahead: Option<String>,
+    pub kind: ConflictKind,
+    // シフト項と還元項，または2つの還元項
+    pub items: Vec<Item>,
+    // 項それぞれの反例
+    pub examples: Vec<String>,
+}
+
+impl Conflict {
+    // 衝突の原因として指す規則．シフト項と衝突した還元項，または後に書かれた還元項
+    pub fn get_rule_number(&self) -> usize {
+        self.items.last().unwrap().get_rule_number()
+    }
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            ConflictKind::ShiftReduce => "shift/reduce",
+            ConflictKind::ReduceReduce => "reduce/reduce",
+        };
+        let lookahead = self.lookahead.as_deref().unwrap_or("end of input");
+
+        write!(
+            f,
+            "{} conflict in state {} on {}:",
+            kind, self.state, lookahead
+        )?;
+        for item in &self.items {
+            write!(f, "\n    {:?}", item)?;
+        }
+        for example in &self.examples {
+            write!(f, "\n  {}", example)?;
+        }
+
+        Ok(())
+    }
+}
+
+// 予期している衝突の数．%expectと%expect-rrに当たる
+pub struct Expect {
+    shift_reduce: usize,
+    reduce_reduce: usize,
+}
+
+impl Expect {
+    pub fn from(arg: Option<TokenTree>) -> Self {
+        let mut ret = Self {
+            shift_reduce: 0,
+            reduce_reduce: 0,
+        };
+
+        if let Some(Group(grp)) = arg {
+            let mut it = grp.stream().into_iter();
+
+            while let Some(tt) = it.next() {
+                let kind = get_ident_string(Some(tt));
+                // macro_rulesの$l:literalは区切りのないGroupに包まれて渡される
+                let n = match it.next() {
+                    Some(Group(grp)) if grp.delimiter() == Delimiter::None => {
+                        grp.stream().to_string()
+                    }
+                    Some(Literal(lit)) => lit.to_string(),
+                    _ => panic!(),
+                };
+                let n = n.parse().unwrap_or_else(|_| {
+                    panic!("The number of conflicts must be an integer! ({})", n)
+                });
+
+                match &kind[..] {
+                    "ShiftReduce" => ret.shift_reduce = n,
+                    "ReduceReduce" => ret.reduce_reduce = n,
+                    _ => panic!(
+                        "The kind of conflicts must be ShiftReduce or ReduceReduce! ({})",
+                        kind
+                    ),
+                }
+            }
+
+            ret
+        } else {
+            panic!()
+        }
+    }
+
+    // 衝突の数が予期した通りでなければ，その数を報告する
+    pub fn check(&self, conflicts: &[Conflict]) -> Result<(), String> {
+        let count = |kind| conflicts.iter().filter(|x| x.kind == kind).count();
+        let shift_reduce = count(ConflictKind::ShiftReduce);
+        let reduce_reduce = count(ConflictKind::ReduceReduce);
+
+        if shift_reduce == self.shift_reduce && reduce_reduce == self.reduce_reduce {
+            return Ok(());
+        }
+
+        Err(format!(
+            "{} shift/reduce conflicts (expected {}) and {} reduce/reduce conflicts (expected {})",
+            shift_reduce, self.shift_reduce, reduce_reduce, self.reduce_reduce
+        ))
+    }
+}
@@ -0,0 +1,148 @@
+use crate::action::{self, Actions};
+use crate::item::Item;
+
+// 右辺の欄の省略記法 Option<X>, Vec<X>, Vec1<X>, Sep<X, S> を補助の非終端記号に展開する．
+// 補助記号の名前は_を含むので，利用者の記号とは衝突しない．
+// リストは右再帰で先頭に要素を加えながら組み立てるので，LL1でも扱える
+
+// Vec<(補助記号, Vec<(規則名, 右辺)>)>
+type Rules = Vec<(String, Vec<(&'static str, Vec<String>)>)>;
+
+// 欄kind<args>を表す補助記号と，展開に必要な補助記号の規則
+pub fn expand(kind: &str, args: &[String]) -> (String, Rules) {
+    let arity = if kind == "Sep" { 2 } else { 1 };
+    if args.len() != arity {
+        panic!(
+            "{} takes {} symbol(s)! ({}<{}>)",
+            kind,
+            arity,
+            kind,
+            args.join(", ")
+        );
+    }
+
+    let x = &args[0];
+    let vec_x = format!("Vec_{}", x);
+    let vec_rules = (
+        vec_x.clone(),
+        vec![("Nil", vec![]), ("Cons", vec![x.clone(), vec_x.clone()])],
+    );
+
+    match kind {
+        "Option" => {
+            let symbol = format!("Option_{}", x);
+            let rules = vec![(
+                symbol.clone(),
+                vec![("None", vec![]), ("Some", vec![x.clone()])],
+            )];
+            (symbol, rules)
+        }
+
+        "Vec" => (vec_x, vec![vec_rules]),
+
+        "Vec1" => {
+            let symbol = format!("Vec1_{}", x);
+            let rules = vec![
+                (symbol.clone(), vec![("Cons", vec![x.clone(), vec_x])]),
+                vec_rules,
+            ];
+            (symbol, rules)
+        }
+
+        "Sep" => {
+            let s = &args[1];
+            let symbol = format!("Sep_{}_{}", x, s);
+            let tail = format!("SepTail_{}_{}", x, s);
+            let rules = vec![
+                (
+                    symbol.clone(),
+                    vec![("Nil", vec![]), ("Cons", vec![x.clone(), tail.clone()])],
+                ),
+                (
+                    tail.clone(),
+                    vec![
+                        ("Nil", vec![]),
+                        ("Cons", vec![s.clone(), x.clone(), tail.clone()]),
+                    ],
+                ),
+            ];
+            (symbol, rules)
+        }
+
+        _ => panic!(
+            "The repetition must be Option, Vec, Vec1 or Sep! ({}<{}>)",
+            kind,
+            args.join(", ")
+        ),
+    }
+}
+
+// 補助記号なら(種類, 要素の記号)
+fn get_helper_kind(symbol: &str) -> Option<(&str, &str)> {
+    let (kind, rest) = symbol.split_once('_')?;
+    let x = rest.split('_').next()?;
+
+    match kind {
+        "Option" | "Vec" | "Vec1" | "Sep" | "SepTail" => Some((kind, x)),
+        _ => None,
+    }
+}
+
+pub fn is_helper(symbol: &str) -> bool {
+    get_helper_kind(symbol).is_some()
+}
+
+// 非終端記号の値の型
+pub fn get_value_type(symbol: &str) -> String {
+    match get_helper_kind(symbol) {
+        Some(("Option", x)) => format!("Option<Box<{}>>", x),
+        Some((_, x)) => format!("std::collections::VecDeque<{}>", x),
+        None => symbol.to_string(),
+    }
+}
+
+// 意味動作から見た，記号symbolに束縛されたi番目の子の値．
+// Option<X>とVec<X>の欄は，木の欄と違ってBoxに包まない
+pub fn get_action_value(symbol: &str, i: usize) -> String {
+    match get_helper_kind(symbol) {
+        Some(("Option", _)) => format!("t{}.map(|x| *x)", i),
+        Some(_) => format!("Vec::from(t{})", i),
+        None => format!("t{}", i),
+    }
+}
+
+// itemの規則で還元したときの値の式．子の値はt0, t1, ...に束縛されているとする．
+// 意味動作があれば，後でそれに置き換わる目印を置く
+pub fn construct(item: &Item, actions: &Actions) -> String {
+    if actions.contains_key(&item.get_rule_number()) {
+        return action::placeholder(item.get_rule_number());
+    }
+
+    let lhs = item.get_lhs();
+    let n = item.get_rhs().len();
+
+    if is_helper(lhs) {
+        return match item.get_rule_name() {
+            "None" => "None".to_string(),
+            "Some" => "Some(Box::new(t0))".to_string(),
+            "Nil" => "std::collections::VecDeque::new()".to_string(),
+            // 最後の子のリストの先頭に，その直前の子を加える
+            "Cons" => format!("{{ let mut v = t{}; v.push_front(t{}); v }}", n - 1, n - 2),
+            _ => panic!(),
+        };
+    }
+
+    let fields = item
+        .get_rhs()
+        .iter()
+        .enumerate()
+        .map(|(i, symbol)| match get_helper_kind(symbol) {
+            Some(("Option", _)) => format!("t{}", i),
+            Some(_) => format!("Vec::from(t{})", i),
+            None => format!("Box::new(t{})", i),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!("{}::{}({})", lhs, item.get_rule_name(), fields)
+}
@@ -0,0 +1,94 @@
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+use std::collections::HashMap;
+
+// 文法の誤り．compile_error!になる
+pub struct GrammarError {
+    pub location: Location,
+    pub message: String,
+}
+
+// 誤りではないが，おそらく意図していないもの．警告になる
+pub struct GrammarWarning {
+    pub span: Span,
+    pub message: String,
+}
+
+pub enum Location {
+    // 開始記号
+    Start,
+    // 規則名
+    Rule(usize),
+    Span(Span),
+}
+
+pub fn compile_errors(
+    errors: &[GrammarError],
+    rule_spans: &HashMap<usize, Span>,
+    start_span: Span,
+) -> TokenStream {
+    errors
+        .iter()
+        .flat_map(|error| {
+            let span = match error.location {
+                Location::Start => start_span,
+                Location::Rule(x) => rule_spans.get(&x).copied().unwrap_or(start_span),
+                Location::Span(span) => span,
+            };
+
+            compile_error(&error.message, span)
+        })
+        .collect()
+}
+
+pub fn compile_warnings(warnings: &[GrammarWarning]) -> TokenStream {
+    warnings
+        .iter()
+        .flat_map(|warning| compile_warning(&warning.message, warning.span))
+        .collect()
+}
+
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut literal = Literal::string(message);
+    literal.set_span(span);
+
+    let mut group = Group::new(Delimiter::Parenthesis, TokenTree::from(literal).into());
+    group.set_span(span);
+
+    TokenStream::from_iter([
+        TokenTree::from(Ident::new("compile_error", span)),
+        punct('!', span),
+        TokenTree::from(group),
+        punct(';', span),
+    ])
+}
+
+// 安定版のproc_macroには警告を出す手段がないので，
+// 非推奨の項目をspanの位置で使ってdeprecatedの警告を出させる
+fn compile_warning(message: &str, span: Span) -> TokenStream {
+    let mut body: TokenStream =
+        format!("#[deprecated(note = {:?})] struct GrammarWarning;", message)
+            .parse()
+            .unwrap();
+
+    body.extend([
+        TokenTree::from(Ident::new("let", span)),
+        TokenTree::from(Ident::new("_", span)),
+        punct('=', span),
+        TokenTree::from(Ident::new("GrammarWarning", span)),
+        punct(';', span),
+    ]);
+
+    let mut ret: TokenStream = "const _: () =".parse().unwrap();
+    ret.extend([
+        TokenTree::from(Group::new(Delimiter::Brace, body)),
+        punct(';', span),
+    ]);
+
+    ret
+}
+
+fn punct(c: char, span: Span) -> TokenTree {
+    let mut punct = Punct::new(c, Spacing::Alone);
+    punct.set_span(span);
+    TokenTree::from(punct)
+}
@@ -18,6 +18,10 @@ impl Item {
         }
     }
 
+    pub fn get_rule_number(&self) -> usize {
+        self.rule_id.0
+    }
+
     pub fn get_rule_name(&self) -> &str {
         &self.rule_id.1
     }
@@ -30,6 +34,10 @@ impl Item {
         &self.rhs
     }
 
+    pub fn get_pointer(&self) -> usize {
+        self.pointer
+    }
+
     pub fn get_symbol_under_pointer(&self) -> Option<String> {
         self.rhs.get(self.pointer).cloned()
     }
@@ -46,6 +54,11 @@ impl Item {
         self.pointer == self.rhs.len()
     }
 
+    // 初期itemとポインタが左端にないitemがカーネル項
+    pub fn is_kernel(&self) -> bool {
+        self.pointer > 0 || self.lhs == "S_"
+    }
+
     pub fn shift(&mut self) -> Option<String> {
         if let Some(s) = self.get_symbol_under_pointer() {
             self.pointer += 1;
@@ -121,6 +134,10 @@ impl LR0Closure {
     pub fn len(&self) -> usize {
         self.0.len()
     }
+
+    pub fn kernel(&self) -> impl Iterator<Item = &Item> {
+        self.0.iter().filter(|item| item.is_kernel())
+    }
 }
 
 impl<'a> std::iter::IntoIterator for &'a LR0Closure {
@@ -1,9 +1,17 @@
 use proc_macro::*;
 
+mod action;
 mod common;
+mod conflict;
+mod ebnf;
+mod error;
 mod item;
+mod mode;
+mod precedence;
 mod rule;
+mod spanned;
 mod token;
+mod validate;
 
 mod parser;
 
@@ -13,19 +21,108 @@ pub fn impl_lr_parser(input: TokenStream) -> TokenStream {
 
     let algorithm = common::get_ident_string(iter.next());
 
-    let start_symbol = common::get_ident_string(iter.next());
+    let start_tt = iter.next();
+    let start_span = start_tt.as_ref().map(|x| x.span()).unwrap();
+    let start_symbol = common::get_ident_string(start_tt);
 
-    let terminal_symbols = token::get_terminal_symbols(iter.next());
+    let (mut terminal_symbols, token_spans) = token::get_terminal_symbols(iter.next());
 
-    let (nonterminal_symbols, map_lhs2items) = rule::parse_rule(&start_symbol, iter.next());
+    let (nonterminal_symbols, map_lhs2items, rule_info) =
+        rule::parse_rule(&start_symbol, iter.next());
 
-    parser::create_parser(
+    // 右辺で使われたerrorは，誤りから回復するための疑似字句
+    if map_lhs2items
+        .values()
+        .flatten()
+        .any(|x| x.get_rhs().iter().any(|symbol| symbol == "error"))
+    {
+        terminal_symbols.insert("error".to_string());
+    }
+
+    let precedence = precedence::Precedence::from(
+        iter.next(),
+        &terminal_symbols,
+        &map_lhs2items,
+        &rule_info.prec_symbols,
+    );
+
+    let expect = conflict::Expect::from(iter.next());
+
+    let lazy = common::get_lazy_flag(iter.next());
+
+    let (mut errors, warnings) = validate::validate(
         &algorithm,
         &start_symbol,
         &terminal_symbols,
+        &token_spans,
         &nonterminal_symbols,
         &map_lhs2items,
-    )
-    .parse()
-    .unwrap()
+        &rule_info,
+    );
+    errors.extend(validate::validate_precedence(
+        &algorithm,
+        &rule_info,
+        &precedence,
+    ));
+
+    // 誤りのある文法からは表を作らない
+    let (code, errors) = if matches!(&algorithm[..], "LL" | "Packrat") {
+        parser::create_grammar_check(errors, lazy)
+    } else if errors.is_empty() {
+        parser::create_parser(
+            &algorithm,
+            &start_symbol,
+            &terminal_symbols,
+            &nonterminal_symbols,
+            &map_lhs2items,
+            &parser::Options {
+                actions: &rule_info.actions,
+                precedence: &precedence,
+                expect: &expect,
+                lazy,
+            },
+        )
+    } else {
+        parser::create_stub(
+            &start_symbol,
+            errors,
+            lazy,
+            matches!(&algorithm[..], "LR0" | "SLR" | "LALR" | "LR1" | "PGM"),
+        )
+    };
+
+    let mut ret = action::splice(code.parse().unwrap(), &rule_info.actions);
+    ret.extend(error::compile_errors(&errors, &rule_info.spans, start_span));
+    ret.extend(error::compile_warnings(&warnings));
+
+    ret
+}
+
+// LLとPackratの意味動作．$Nを右辺の値の組tupleのN番目に置き換える
+#[proc_macro]
+pub fn impl_action(input: TokenStream) -> TokenStream {
+    let mut iter = input.into_iter();
+
+    let tuple = iter.next().unwrap();
+    let action = iter.next().unwrap();
+
+    action::replace_fields(action.into(), &|n| {
+        TokenStream::from_iter([
+            tuple.clone(),
+            TokenTree::from(Punct::new('.', Spacing::Alone)),
+            TokenTree::from(Literal::usize_unsuffixed(n)),
+        ])
+    })
+}
+
+// 字句を持たないモードが使われていればcompile_error!にする
+#[proc_macro]
+pub fn check_modes(input: TokenStream) -> TokenStream {
+    mode::check_modes(input)
+}
+
+// 木の非終端記号の位置
+#[proc_macro]
+pub fn impl_spanned(input: TokenStream) -> TokenStream {
+    spanned::impl_spanned(input).parse().unwrap()
 }
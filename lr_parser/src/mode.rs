@@ -0,0 +1,35 @@
+use proc_macro::{Ident, Span, TokenStream, TokenTree};
+use std::collections::{HashMap, HashSet};
+
+use crate::error::{self, GrammarError, Location};
+
+// 字句を持たないモードの名前を，書かれた位置の誤りにする．
+// 実行時に字句解析器を作るときに気付いても遅い
+pub fn check_modes(input: TokenStream) -> TokenStream {
+    let mut iter = input.into_iter();
+
+    // TOKENで字句が属するモード．INITIALは字句がなくても存在する
+    let mut modes: HashSet<_> = idents(iter.next()).map(|x| x.to_string()).collect();
+    modes.insert("INITIAL".to_string());
+
+    // WHITESPACE，SKIP，push，switchで使われたモード
+    let errors: Vec<_> = idents(iter.next())
+        .filter(|x| !modes.contains(&x.to_string()))
+        .map(|x| GrammarError {
+            location: Location::Span(x.span()),
+            message: format!("The mode has no token! ({})", x),
+        })
+        .collect();
+
+    error::compile_errors(&errors, &HashMap::new(), Span::call_site())
+}
+
+fn idents(arg: Option<TokenTree>) -> impl Iterator<Item = Ident> {
+    match arg {
+        Some(TokenTree::Group(grp)) => grp.stream().into_iter().map(|tt| match tt {
+            TokenTree::Ident(ident) => ident,
+            _ => panic!(),
+        }),
+        _ => panic!(),
+    }
+}
@@ -1,11 +1,23 @@
 use std::collections::{HashMap, HashSet};
 
+use crate::action::Actions;
+use crate::conflict::{Conflict, ConflictKind, Expect};
+use crate::ebnf;
+use crate::error::{GrammarError, Location};
 use crate::item::Item;
+use crate::precedence::{Precedence, Resolution};
 
+mod counterexample;
+mod earley;
 mod first_sets;
 mod follow_sets;
+mod forest;
+mod glr;
+mod lalr;
+mod ll1;
 mod lr0;
 mod lr1;
+mod pgm;
 mod slr;
 
 #[derive(Debug)]
@@ -13,6 +25,19 @@ pub enum Action {
     Shift(usize),
     Reduce(Item),
     Accept,
+    // nonassocで衝突を解決した欄．構文エラーになる
+    Error,
+}
+
+// Map<状態, Map<先読み記号, 動作>>
+pub type ParsingTable = HashMap<usize, HashMap<Option<String>, Action>>;
+
+// 文法の他に，生成するパーサを決める指定
+pub struct Options<'a> {
+    pub actions: &'a Actions,
+    pub precedence: &'a Precedence,
+    pub expect: &'a Expect,
+    pub lazy: bool,
 }
 
 pub fn create_parser(
@@ -21,57 +46,316 @@ pub fn create_parser(
     terminal_symbols: &HashSet<String>,
     nonterminal_symbols: &HashSet<String>,
     map_lhs2items: &HashMap<String, HashSet<Item>>,
-) -> String {
-    let result = match &*algorithm {
+    options: &Options,
+) -> (String, Vec<GrammarError>) {
+    let &Options {
+        actions,
+        precedence,
+        expect,
+        lazy,
+    } = options;
+
+    if algorithm == "GLR" {
+        let code = glr::automaton::create_glr_parser(
+            start_symbol,
+            terminal_symbols,
+            nonterminal_symbols,
+            map_lhs2items,
+            actions,
+        );
+        return (code, vec![]);
+    }
+
+    if algorithm == "LL1" {
+        return ll1::table_driven::create_ll1_parser(
+            start_symbol,
+            terminal_symbols,
+            nonterminal_symbols,
+            map_lhs2items,
+            actions,
+            lazy,
+        );
+    }
+
+    if algorithm == "Earley" {
+        let code = earley::create_earley_parser(
+            start_symbol,
+            terminal_symbols,
+            nonterminal_symbols,
+            map_lhs2items,
+            actions,
+        );
+        return (code, vec![]);
+    }
+
+    let (parsing_table, mut conflicts) = match algorithm {
         "LR0" => lr0::parsing_table::compute_lr0_parsing_table(
             start_symbol,
             terminal_symbols,
+            nonterminal_symbols,
             map_lhs2items,
+            precedence,
         ),
 
         "SLR" => slr::parsing_table::compute_slr_parsing_table(
             start_symbol,
             nonterminal_symbols,
             map_lhs2items,
+            precedence,
+        ),
+
+        "LALR" => lalr::parsing_table::compute_lalr_parsing_table(
+            start_symbol,
+            nonterminal_symbols,
+            map_lhs2items,
+            precedence,
         ),
 
         "LR1" => lr1::parsing_table::compute_lr1_parsing_table(
             start_symbol,
             nonterminal_symbols,
             map_lhs2items,
+            precedence,
+        ),
+
+        "PGM" => pgm::parsing_table::compute_pgm_parsing_table(
+            start_symbol,
+            nonterminal_symbols,
+            map_lhs2items,
+            precedence,
         ),
 
         _ => panic!(),
     };
 
-    match result {
-        Ok(parsing_table) => {
-            let mut ret = String::new();
+    // 状態と先読み記号の順に報告する
+    conflicts.sort_by(|x, y| (x.state, &x.lookahead).cmp(&(y.state, &y.lookahead)));
+
+    let errors = match expect.check(&conflicts) {
+        Ok(()) => vec![],
+
+        Err(summary) => {
+            // 衝突はそれぞれ原因の規則を指す
+            let mut errors = vec![GrammarError {
+                location: Location::Start,
+                message: summary,
+            }];
+            for conflict in &conflicts {
+                errors.push(GrammarError {
+                    location: Location::Rule(conflict.get_rule_number()),
+                    message: conflict.to_string(),
+                });
+            }
+
+            if lazy {
+                return create_stub(start_symbol, errors, lazy, true);
+            }
+
+            errors
+        }
+    };
 
-            ret.push_str(&enum_status(start_symbol));
-            ret.push_str(&struct_automaton());
-            ret.push_str(&impl_automaton(start_symbol, &parsing_table));
-            ret.push_str(&impl_yacc(start_symbol));
-            ret.push_str(&enum_tree(terminal_symbols, nonterminal_symbols));
-            ret.push_str(&impl_tree(terminal_symbols));
+    let mut ret = String::new();
 
-            ret
-        }
+    ret.push_str(&enum_status(start_symbol));
+    ret.push_str(&struct_automaton());
+    ret.push_str(&impl_automaton(
+        start_symbol,
+        &parsing_table,
+        terminal_symbols,
+        actions,
+    ));
+    ret.push_str(&impl_yacc(start_symbol));
+    ret.push_str(&impl_parser(start_symbol));
+    ret.push_str(&enum_tree(terminal_symbols, nonterminal_symbols));
+    ret.push_str(&impl_tree(terminal_symbols));
+
+    (ret, errors)
+}
 
-        Err(error_message) => {
-            format!(
-                "
+// 文法に誤りがあるときのparse．lazyなら誤りを実行時に報告し，
+// そうでなければ誤りはcompile_error!になるので呼ばれることはない．
+// recoverならLRの表と同じくparse_recoverも作る
+pub fn create_stub(
+    start_symbol: &str,
+    errors: Vec<GrammarError>,
+    lazy: bool,
+    recover: bool,
+) -> (String, Vec<GrammarError>) {
+    let (body, recover_body) = if lazy {
+        let error = grammar_error(&errors);
+        (
+            format!("Err({})", error),
+            format!("(None, vec![{}])", error),
+        )
+    } else {
+        ("unreachable!()".to_string(), "unreachable!()".to_string())
+    };
+
+    let mut code = format!(
+        "
+#[allow(clippy::ptr_arg)]
 impl Yacc {{
-    pub fn parse(v: &Vec<Token>) -> Result<{}, String> {{
-        Err(\"{}\".to_string())
+    pub fn parse(_: &str, _: &Vec<Token>) -> Result<{}, error::ParseError> {{
+        {}
     }}
 }}",
-                start_symbol, error_message
-            )
-        }
+        start_symbol, body
+    );
+
+    if recover {
+        code.push_str(&format!(
+            "
+#[allow(clippy::ptr_arg)]
+impl Yacc {{
+    pub fn parse_recover(_: &str, _: &Vec<Token>) -> (Option<{0}>, Vec<error::ParseError>) {{
+        {1}
+    }}
+}}
+
+impl Parser {{
+    pub fn parse_recover(_: &str) -> (Option<{0}>, Vec<error::ParseError>) {{
+        {1}
+    }}
+}}",
+            start_symbol, recover_body
+        ));
+    }
+
+    if lazy {
+        (code, vec![])
+    } else {
+        (code, errors)
+    }
+}
+
+// LLとPackratのパーサはsyntax!が作るので，文法を調べた結果だけを返す．
+// lazyなら誤りを実行時に報告し，そうでなければコンパイルエラーにする
+pub fn create_grammar_check(errors: Vec<GrammarError>, lazy: bool) -> (String, Vec<GrammarError>) {
+    let body = if lazy && !errors.is_empty() {
+        format!("Err({})", grammar_error(&errors))
+    } else {
+        "Ok(())".to_string()
+    };
+
+    let code = format!(
+        "
+impl Yacc {{
+    fn check_grammar() -> Result<(), error::ParseError> {{
+        {}
+    }}
+}}",
+        body
+    );
+
+    if lazy {
+        (code, vec![])
+    } else {
+        (code, errors)
     }
 }
 
+fn grammar_error(errors: &[GrammarError]) -> String {
+    let error_message = errors
+        .iter()
+        .map(|x| &x.message[..])
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        "error::ParseError::Grammar({:?}.to_string())",
+        error_message
+    )
+}
+
+// 表のstateの行のlookaheadの欄にactionを加える．itemsはstateの項．
+// shift/reduce衝突は優先順位と結合性で解決する．解決できない衝突は記録した上で，
+// yaccと同じくシフトか，番号の小さい規則による還元を選ぶ
+fn insert_action(
+    map: &mut HashMap<Option<String>, Action>,
+    state: usize,
+    items: &[Item],
+    lookahead: Option<String>,
+    action: Action,
+    precedence: &Precedence,
+    conflicts: &mut Vec<Conflict>,
+) {
+    let action = match (map.remove(&lookahead), action) {
+        (None, action) => action,
+
+        (Some(Action::Shift(to)), Action::Reduce(item))
+        | (Some(Action::Reduce(item)), Action::Shift(to)) => {
+            match lookahead
+                .as_deref()
+                .and_then(|x| precedence.resolve(&item, x))
+            {
+                Some(Resolution::Shift) => Action::Shift(to),
+                Some(Resolution::Reduce) => Action::Reduce(item),
+                Some(Resolution::Error) => Action::Error,
+                None => {
+                    let mut involved_items: Vec<_> = items
+                        .iter()
+                        .filter(|x| x.get_symbol_under_pointer() == lookahead)
+                        .cloned()
+                        .collect();
+                    involved_items.push(item);
+
+                    conflicts.push(Conflict {
+                        state,
+                        lookahead: lookahead.clone(),
+                        kind: ConflictKind::ShiftReduce,
+                        items: involved_items,
+                        examples: vec![],
+                    });
+
+                    Action::Shift(to)
+                }
+            }
+        }
+
+        (Some(Action::Reduce(item1)), Action::Reduce(item2)) => {
+            let (item1, item2) = if item1.get_rule_number() < item2.get_rule_number() {
+                (item1, item2)
+            } else {
+                (item2, item1)
+            };
+
+            conflicts.push(Conflict {
+                state,
+                lookahead: lookahead.clone(),
+                kind: ConflictKind::ReduceReduce,
+                items: vec![item1.clone(), item2],
+                examples: vec![],
+            });
+
+            Action::Reduce(item1)
+        }
+
+        // nonassocで空けた欄を取り合う還元
+        (Some(Action::Error), Action::Reduce(_)) => {
+            let mut involved_items: Vec<_> = items
+                .iter()
+                .filter(|x| x.is_reducible() && x.get_lhs() != "S_")
+                .cloned()
+                .collect();
+            involved_items.sort();
+
+            conflicts.push(Conflict {
+                state,
+                lookahead: lookahead.clone(),
+                kind: ConflictKind::ReduceReduce,
+                items: involved_items,
+                examples: vec![],
+            });
+
+            Action::Error
+        }
+
+        (Some(existing), _) => existing,
+    };
+
+    map.insert(lookahead, action);
+}
+
 fn enum_status(start_symbol: &str) -> String {
     format!(
         "
@@ -89,13 +373,17 @@ struct Automaton {
     input: std::collections::VecDeque<Tree>,
     state_stack: Vec<usize>,
     symbol_stack: Vec<Tree>,
+    // 誤りから回復した後，あといくつ字句をシフトするまで次の誤りを報告しないか
+    errflag: usize,
 }"
     .to_string()
 }
 
 fn impl_automaton(
     start_symbol: &str,
-    parsing_table: &HashMap<usize, HashMap<Option<String>, Action>>,
+    parsing_table: &ParsingTable,
+    terminal_symbols: &HashSet<String>,
+    actions: &Actions,
 ) -> String {
     let mut ret = String::new();
 
@@ -106,9 +394,16 @@ impl Automaton {",
 
     ret.push_str(&fn_new());
 
-    ret.push_str(&fn_step(parsing_table, start_symbol));
+    ret.push_str(&fn_step(
+        parsing_table,
+        terminal_symbols,
+        start_symbol,
+        actions,
+    ));
 
-    ret.push_str(&fn_run(start_symbol));
+    ret.push_str(&fn_can_shift_error(parsing_table));
+
+    ret.push_str(&fn_run(start_symbol, terminal_symbols.contains("error")));
 
     ret.push_str(
         "
@@ -127,26 +422,33 @@ fn fn_new() -> String {
             input,
             state_stack: vec![0],
             symbol_stack: vec![],
+            errflag: 0,
         }
     }"
     .to_string()
 }
 
-fn shift(from: usize, x: &str, to: usize) -> String {
+fn shift(from: usize, x: &str, to: usize, is_token: bool) -> String {
+    // 回復の後は，字句をシフトするたびに報告の再開に近づく
+    let errflag = if is_token {
+        "
+                self.errflag = self.errflag.saturating_sub(1);"
+    } else {
+        ""
+    };
+
     format!(
         "
             (Some({}), Some(Tree::{}(_))) => {{
                 self.state_stack.push({});
-                self.symbol_stack.push(self.input.pop_front().unwrap());
+                self.symbol_stack.push(self.input.pop_front().unwrap());{}
                 return Ok(Status::Running);
             }}",
-        from, x, to
+        from, x, to, errflag
     )
 }
 
-fn reduce(from: usize, x: &str, item: &Item) -> String {
-    let field_num = item.get_rhs().len();
-
+fn reduce(from: usize, x: &str, item: &Item, actions: &Actions) -> String {
     let s1 = item
         .get_rhs()
         .iter()
@@ -156,17 +458,13 @@ fn reduce(from: usize, x: &str, item: &Item) -> String {
             s.push_str(&format!("Some(Tree::{}(t{})), ", symbol, i));
             s
         });
-    let s2 = (0..field_num).fold("".to_string(), |mut s, i| {
-        s.push_str(&format!("Box::new(t{}), ", i));
-        s
-    });
 
     format!(
         "
             (Some({}), Some(Tree::{}(_))) => {{
                 if let ({}) = ({}) {{
                     if let ({}) = ({}) {{
-                        self.input.push_front(Tree::{}({}::{}({})));
+                        self.input.push_front(Tree::{}({}));
                         return Ok(Status::Running);
                     }}
                 }}
@@ -178,9 +476,7 @@ fn reduce(from: usize, x: &str, item: &Item) -> String {
         s1,
         "self.symbol_stack.pop(), ".repeat(item.get_rhs().len()),
         item.get_lhs(),
-        item.get_lhs(),
-        item.get_rule_name(),
-        s2
+        ebnf::construct(item, actions)
     )
 }
 
@@ -199,23 +495,31 @@ fn accept(from: usize, start_symbol: &str) -> String {
 }
 
 fn fn_step(
-    parsing_table: &HashMap<usize, HashMap<Option<String>, Action>>,
+    parsing_table: &ParsingTable,
+    terminal_symbols: &HashSet<String>,
     start_symbol: &str,
+    actions: &Actions,
 ) -> String {
     let mut ret = String::new();
 
     ret.push_str(
         "
-    fn step(&mut self) -> Result<Status, String> {
+    // 意味動作が右辺の値を使わないこともある
+    #[allow(unused_variables)]
+    // 失敗したら残りの入力の長さと，その状態で読めた終端記号と，その状態を返す
+    fn step(&mut self) -> Result<Status, (usize, &'static [&'static str], Option<usize>)> {
         match (self.state_stack.last(), self.input.front()) {",
     );
 
     for (from, map) in parsing_table {
         for pair in map {
             let s = match pair {
-                (Some(x), Action::Shift(to)) => shift(*from, x, *to),
-                (Some(x), Action::Reduce(item)) => reduce(*from, x, item),
+                (Some(x), Action::Shift(to)) => {
+                    shift(*from, x, *to, terminal_symbols.contains(x) && x != "error")
+                }
+                (Some(x), Action::Reduce(item)) => reduce(*from, x, item, actions),
                 (None, Action::Accept) => accept(*from, start_symbol),
+                (_, Action::Error) => continue,
                 _ => panic!(),
             };
 
@@ -229,36 +533,194 @@ fn fn_step(
             _ => {}
         }
 
-        Err(\"ParseError!\".to_string())
+        let expected: &[&str] = match self.state_stack.last() {",
+    );
+
+    let mut states: Vec<_> = parsing_table.iter().collect();
+    states.sort_by_key(|(x, _)| *x);
+
+    for (from, map) in states {
+        let mut expected: Vec<_> = map
+            .iter()
+            .filter_map(|pair| match pair {
+                (_, Action::Error) => None,
+                (Some(x), _) if terminal_symbols.contains(x) && x != "error" => {
+                    Some(format!("{:?}", x))
+                }
+                _ => None,
+            })
+            .collect();
+        expected.sort();
+
+        ret.push_str(&format!(
+            "
+            Some({}) => &[{}],",
+            from,
+            expected.join(", ")
+        ));
+    }
+
+    ret.push_str(
+        "
+            _ => &[],
+        };
+
+        Err((self.input.len(), expected, self.state_stack.last().copied()))
     }",
     );
 
     ret
 }
 
-fn fn_run(start_symbol: &str) -> String {
+// errorをシフトできる状態
+fn fn_can_shift_error(parsing_table: &ParsingTable) -> String {
+    let mut states: Vec<_> = parsing_table
+        .iter()
+        .filter(|(_, map)| matches!(map.get(&Some("error".to_string())), Some(Action::Shift(_))))
+        .map(|(from, _)| from.to_string())
+        .collect();
+    states.sort();
+
+    let body = if states.is_empty() {
+        "false".to_string()
+    } else {
+        format!("matches!(state, {})", states.join(" | "))
+    };
+
     format!(
         "
-    fn run(&mut self) -> Result<{}, String> {{
+
+    fn can_shift_error(state: usize) -> bool {{
+        {}
+    }}",
+        body
+    )
+}
+
+fn fn_run(start_symbol: &str, uses_error: bool) -> String {
+    // errorを使わない文法では，戻れる状態はない
+    let shift_error = if uses_error {
+        "
+
+            self.input.push_front(Tree::error(error::Recovered { error }));"
+    } else {
+        ""
+    };
+
+    format!(
+        "
+
+    // recoverならyaccと同じく，errorをシフトできる状態まで戻ってerrorをシフトし，
+    // 読める字句が来るまで入力を捨てて続ける．3字句シフトするまでは次の誤りを報告しない
+    #[allow(clippy::ptr_arg)]
+    fn run(
+        &mut self,
+        s: &str,
+        v: &Vec<Token>,
+        recover: bool,
+    ) -> (Option<{}>, Vec<error::ParseError>) {{
+        let mut errors = vec![];
+
         loop {{
-            if let Status::Finished(t) = self.step()? {{
-                return Ok(t);
+            let (rest, expected, state) = match self.step() {{
+                Ok(Status::Finished(t)) => return (Some(t), errors),
+                Ok(Status::Running) => continue,
+                Err(e) => e,
+            }};
+
+            // 残りの入力は末尾のF_を含む
+            let error = Yacc::error(s, v, v.len() + 1 - rest, expected, state);
+
+            if !recover {{
+                errors.push(error);
+                return (None, errors);
             }}
+
+            if self.errflag == 0 {{
+                errors.push(error.clone());
+            }}
+
+            // 回復した直後なら，読めない字句を捨てる
+            if self.errflag == 3 {{
+                match self.input.pop_front() {{
+                    Some(Tree::F_(_)) | None => return (None, errors),
+                    _ => continue,
+                }}
+            }}
+            self.errflag = 3;
+
+            while let Some(&state) = self.state_stack.last() {{
+                if Self::can_shift_error(state) {{
+                    break;
+                }}
+                self.state_stack.pop();
+                self.symbol_stack.pop();
+            }}
+
+            if self.state_stack.is_empty() {{
+                return (None, errors);
+            }}{}
         }}
     }}",
-        start_symbol
+        start_symbol, shift_error
     )
 }
 
 fn impl_yacc(start_symbol: &str) -> String {
     format!(
         "
+#[allow(clippy::ptr_arg)]
 impl Yacc {{
-    pub fn parse(v: &Vec<Token>) -> Result<{}, String> {{
-        let v: Vec<_> = v.iter().map(|x| Tree::from(x)).collect();
-        let mut automaton = Automaton::new(v);
+    pub fn parse(s: &str, v: &Vec<Token>) -> Result<{0}, error::ParseError> {{
+        match Self::run(s, v, false) {{
+            (Some(t), _) => Ok(t),
+            (None, mut errors) => Err(errors.remove(0)),
+        }}
+    }}
+
+    pub fn parse_recover(s: &str, v: &Vec<Token>) -> (Option<{0}>, Vec<error::ParseError>) {{
+        Self::run(s, v, true)
+    }}
 
-        automaton.run()
+    fn run(s: &str, v: &Vec<Token>, recover: bool) -> (Option<{0}>, Vec<error::ParseError>) {{
+        let input: Vec<_> = v.iter().map(|x| Tree::from(x)).collect();
+        let mut automaton = Automaton::new(input);
+
+        automaton.run(s, v, recover)
+    }}
+}}",
+        start_symbol
+    )
+}
+
+// 誤りから回復して読み続け，木と全ての誤りを位置の順に返す．木は最後まで読めたときだけ返す
+fn impl_parser(start_symbol: &str) -> String {
+    format!(
+        "
+impl Parser {{
+    pub fn parse_recover(s: &str) -> (Option<{0}>, Vec<error::ParseError>) {{
+        let (v, mut errors) = Lex::tokenize_recover(s);
+        let (result, parse_errors) = Yacc::parse_recover(s, &v);
+
+        // 字句の誤りの直後の字句で起きた構文の誤りは，読み飛ばした入力が原因なので報告しない
+        let after_lex_errors: Vec<_> = errors
+            .iter()
+            .filter_map(|x| x.span())
+            .map(|x| {{
+                v.iter()
+                    .map(|t| t.span().start)
+                    .find(|&t| t >= x.end)
+                    .unwrap_or(s.len())
+            }})
+            .collect();
+        errors.extend(
+            parse_errors
+                .into_iter()
+                .filter(|x| !x.span().is_some_and(|x| after_lex_errors.contains(&x.start))),
+        );
+        errors.sort_by_key(|x| x.span().map(|x| x.start));
+
+        (result, errors)
     }}
 }}",
         start_symbol
@@ -270,7 +732,8 @@ fn enum_tree(terminal_symbols: &HashSet<String>, nonterminal_symbols: &HashSet<S
 
     ret.push_str(
         "
-#[derive(Debug)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone)]
 enum Tree {",
     );
 
@@ -279,10 +742,17 @@ enum Tree {",
             continue;
         }
 
+        // error疑似字句の値は，回復した誤り
+        let value_type = if symbol == "error" {
+            "error::Recovered"
+        } else {
+            symbol
+        };
+
         ret.push_str(&format!(
             "
     {}({}),",
-            symbol, symbol
+            symbol, value_type
         ));
     }
 
@@ -294,7 +764,8 @@ enum Tree {",
         ret.push_str(&format!(
             "
     {}({}),",
-            symbol, symbol
+            symbol,
+            ebnf::get_value_type(symbol)
         ));
     }
 
@@ -318,7 +789,7 @@ impl Tree {
     );
 
     for symbol in terminal_symbols {
-        if symbol == "F_" {
+        if symbol == "F_" || symbol == "error" {
             continue;
         }
 
@@ -338,3 +809,56 @@ impl Tree {
 
     ret
 }
+
+// 規則の番号と子の値から左辺の値を作る関数
+fn fn_reduce_tree(map_lhs2items: &HashMap<String, HashSet<Item>>, actions: &Actions) -> String {
+    let mut ret = String::new();
+
+    ret.push_str(
+        "
+#[allow(unused_variables)]
+fn reduce_tree(rule: usize, children: Vec<Tree>) -> Tree {
+    let mut children = children.into_iter();
+    match rule {",
+    );
+
+    for item in map_lhs2items.values().flatten() {
+        if item.get_lhs() == "S_" {
+            continue;
+        }
+
+        let s1 = item
+            .get_rhs()
+            .iter()
+            .enumerate()
+            .fold("".to_string(), |mut s, (i, symbol)| {
+                s.push_str(&format!("Some(Tree::{}(t{})), ", symbol, i));
+                s
+            });
+
+        ret.push_str(&format!(
+            "
+        {} => {{
+            if let ({}) = ({}) {{
+                return Tree::{}({});
+            }}
+        }}",
+            item.get_rule_number(),
+            s1,
+            "children.next(), ".repeat(item.get_rhs().len()),
+            item.get_lhs(),
+            ebnf::construct(item, actions)
+        ));
+    }
+
+    ret.push_str(
+        "
+        _ => {}
+    }
+
+    unreachable!()
+}",
+    );
+
+    ret
+}
@@ -0,0 +1,264 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{conflict::Conflict, item::Item};
+
+// 反例を探す経路の数と，経路を探すときに並べる節点の数の上限
+const MAX_PATHS: usize = 100;
+const MAX_NODES: usize = 10000;
+
+// 衝突の反例を作る．状態0から衝突した状態までの記号列を接頭辞とし，
+// 衝突に関わる項それぞれについて，その項を経由して開始記号から導出される文形式を探す．
+// 最短の経路で見つからなければ，より長い経路を短い順に試す
+pub fn attach_counterexamples(
+    conflicts: &mut [Conflict],
+    transition_map: &HashMap<usize, HashMap<String, usize>>,
+    state_items: &HashMap<usize, Vec<Item>>,
+    first_sets: &HashMap<Vec<String>, HashSet<Option<String>>>,
+) {
+    for conflict in conflicts {
+        let paths = paths(transition_map, conflict.state);
+
+        for item in &conflict.items {
+            // 還元項なら，還元した後に先読み記号が続く文脈を探す
+            let need = if item.is_reducible() {
+                conflict.lookahead.clone()
+            } else {
+                None
+            };
+
+            let result = paths.iter().find_map(|(states, prefix)| {
+                let search = Search {
+                    states,
+                    state_items,
+                    first_sets,
+                };
+
+                search
+                    .derive(
+                        states.len() - 1,
+                        item,
+                        item.get_pointer(),
+                        need.clone(),
+                        None,
+                        &mut HashSet::new(),
+                    )
+                    .map(|(suffix, derivation)| (prefix, suffix, derivation))
+            });
+
+            let example = if let Some((prefix, suffix, derivation)) = result {
+                format!(
+                    "example: {}\n  derivation: {}",
+                    join(prefix, &suffix),
+                    derivation
+                )
+            } else {
+                format!(
+                    "example: {} (no derivation through {:?})",
+                    join(&paths[0].1, &[]),
+                    item
+                )
+            };
+
+            conflict.examples.push(example);
+        }
+    }
+}
+
+// 状態0からstateまでの経路上の状態と記号を，最短のものから順に列挙する
+fn paths(
+    transition_map: &HashMap<usize, HashMap<String, usize>>,
+    state: usize,
+) -> Vec<(Vec<usize>, Vec<String>)> {
+    let mut ret = vec![shortest_path(transition_map, state)];
+
+    // stateに辿り着けない状態には進まない
+    let mut reverse_map: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (&u, map) in transition_map {
+        for &v in map.values() {
+            reverse_map.entry(v).or_default().push(u);
+        }
+    }
+    let mut reachable = HashSet::from([state]);
+    let mut stack = vec![state];
+    while let Some(v) = stack.pop() {
+        for &u in reverse_map.get(&v).into_iter().flatten() {
+            if reachable.insert(u) {
+                stack.push(u);
+            }
+        }
+    }
+
+    // (状態, 一つ前の節点, 記号)を幅優先で並べる
+    let mut nodes: Vec<(usize, usize, &str)> = vec![(0, 0, "")];
+    let mut k = 0;
+
+    while k < nodes.len() && ret.len() < MAX_PATHS {
+        let (u, _, _) = nodes[k];
+
+        if u == state && k > 0 {
+            let mut states = vec![];
+            let mut symbols = vec![];
+            let mut i = k;
+            while i > 0 {
+                states.push(nodes[i].0);
+                symbols.push(nodes[i].2.to_string());
+                i = nodes[i].1;
+            }
+            states.push(0);
+            states.reverse();
+            symbols.reverse();
+
+            if !ret.contains(&(states.clone(), symbols.clone())) {
+                ret.push((states, symbols));
+            }
+        }
+
+        if let Some(map) = transition_map.get(&u) {
+            let mut nexts: Vec<_> = map.iter().collect();
+            nexts.sort();
+
+            for (c, &v) in nexts {
+                if v != 0 && reachable.contains(&v) && nodes.len() < MAX_NODES {
+                    nodes.push((v, k, c));
+                }
+            }
+        }
+
+        k += 1;
+    }
+
+    ret
+}
+
+// 状態0からstateまでの最短経路上の状態と記号
+fn shortest_path(
+    transition_map: &HashMap<usize, HashMap<String, usize>>,
+    state: usize,
+) -> (Vec<usize>, Vec<String>) {
+    let mut parent: HashMap<usize, (usize, &str)> = HashMap::new();
+    let mut vdq = VecDeque::from([0]);
+
+    while let Some(u) = vdq.pop_front() {
+        if u == state {
+            break;
+        }
+
+        if let Some(map) = transition_map.get(&u) {
+            let mut nexts: Vec<_> = map.iter().collect();
+            nexts.sort();
+
+            for (c, &v) in nexts {
+                if v != 0 && !parent.contains_key(&v) {
+                    parent.insert(v, (u, c));
+                    vdq.push_back(v);
+                }
+            }
+        }
+    }
+
+    let mut states = vec![state];
+    let mut symbols = vec![];
+    let mut u = state;
+    while let Some(&(v, c)) = parent.get(&u) {
+        states.push(v);
+        symbols.push(c.to_string());
+        u = v;
+    }
+    states.reverse();
+    symbols.reverse();
+
+    (states, symbols)
+}
+
+fn join(prefix: &[String], suffix: &[String]) -> String {
+    prefix
+        .iter()
+        .map(|x| &x[..])
+        .chain(["•"])
+        .chain(suffix.iter().map(|x| &x[..]))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+struct Search<'a> {
+    // 経路上の状態
+    states: &'a [usize],
+    state_items: &'a HashMap<usize, Vec<Item>>,
+    first_sets: &'a HashMap<Vec<String>, HashSet<Option<String>>>,
+}
+
+impl Search<'_> {
+    // 経路上のj番目の状態にある項itemについて，rhs[from..]の後に続く文脈を
+    // S_の項まで遡って探し，(dotより後ろの記号列, 導出)を返す．
+    // needは還元の直後に読まなければならない終端記号，innerは子の導出
+    fn derive(
+        &self,
+        j: usize,
+        item: &Item,
+        from: usize,
+        need: Option<String>,
+        inner: Option<String>,
+        visited: &mut HashSet<(usize, Item, Option<String>)>,
+    ) -> Option<(Vec<String>, String)> {
+        let rhs = item.get_rhs();
+        let pointer = item.get_pointer();
+        let after = &rhs[from..];
+
+        let need = match need {
+            Some(a) => {
+                let first_set = &self.first_sets[after];
+                if first_set.contains(&Some(a.clone())) {
+                    None
+                } else if first_set.contains(&None) {
+                    Some(a)
+                } else {
+                    return None;
+                }
+            }
+            None => None,
+        };
+
+        let middle = inner.clone().unwrap_or("•".to_string());
+        let frame = format!(
+            "{} -> [ {} ]",
+            item.get_lhs(),
+            rhs[..pointer]
+                .iter()
+                .map(|x| &x[..])
+                .chain([&middle[..]])
+                .chain(after.iter().map(|x| &x[..]))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+
+        if item.get_lhs() == "S_" {
+            let suffix = after.iter().filter(|x| *x != "F_").cloned().collect();
+            return Some((suffix, inner.unwrap_or(frame)));
+        }
+
+        // ポインタが右にある親ほど経路の先頭まで近いので，先に試す
+        let origin = j - pointer;
+        let mut parents: Vec<_> = self.state_items[&self.states[origin]]
+            .iter()
+            .filter(|x| x.get_symbol_under_pointer().as_deref() == Some(item.get_lhs()))
+            .collect();
+        parents.sort_by_key(|x| std::cmp::Reverse(x.get_pointer()));
+
+        for parent in parents {
+            if visited.insert((origin, parent.clone(), need.clone())) {
+                if let Some((suffix, derivation)) = self.derive(
+                    origin,
+                    parent,
+                    parent.get_pointer() + 1,
+                    need.clone(),
+                    Some(frame.clone()),
+                    visited,
+                ) {
+                    return Some(([after, &suffix[..]].concat(), derivation));
+                }
+            }
+        }
+
+        None
+    }
+}
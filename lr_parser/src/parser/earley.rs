@@ -0,0 +1,61 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    action::Actions,
+    item::Item,
+    parser::{enum_tree, forest, impl_tree},
+};
+
+pub fn create_earley_parser(
+    start_symbol: &str,
+    terminal_symbols: &HashSet<String>,
+    nonterminal_symbols: &HashSet<String>,
+    map_lhs2items: &HashMap<String, HashSet<Item>>,
+    actions: &Actions,
+) -> String {
+    let symbol_map = forest::compute_symbol_map(terminal_symbols, nonterminal_symbols);
+
+    let mut ret = String::new();
+
+    ret.push_str(&fn_symbol(terminal_symbols, &symbol_map));
+    ret.push_str(&forest::create_forest_parser(
+        start_symbol,
+        nonterminal_symbols,
+        map_lhs2items,
+        actions,
+        &symbol_map,
+        "earley::parse(&v, &earley_symbol, FOREST_RULES)",
+    ));
+    ret.push_str(&enum_tree(terminal_symbols, nonterminal_symbols));
+    ret.push_str(&impl_tree(terminal_symbols));
+
+    ret
+}
+
+fn fn_symbol(terminal_symbols: &HashSet<String>, symbol_map: &HashMap<&str, usize>) -> String {
+    let mut ret = String::new();
+
+    ret.push_str(
+        "
+fn earley_symbol(t: &Tree) -> usize {
+    match t {",
+    );
+
+    for symbol in terminal_symbols {
+        ret.push_str(&format!(
+            "
+        Tree::{}(_) => {},",
+            symbol,
+            symbol_map[&symbol[..]]
+        ));
+    }
+
+    ret.push_str(
+        "
+        _ => unreachable!(),
+    }
+}",
+    );
+
+    ret
+}
@@ -0,0 +1,232 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::action::Actions;
+use crate::ebnf;
+use crate::item::Item;
+use crate::parser::fn_reduce_tree;
+
+// 記号に番号を付ける
+pub fn compute_symbol_map<'a>(
+    terminal_symbols: &'a HashSet<String>,
+    nonterminal_symbols: &'a HashSet<String>,
+) -> HashMap<&'a str, usize> {
+    terminal_symbols
+        .iter()
+        .chain(nonterminal_symbols)
+        .map(|s| &s[..])
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .enumerate()
+        .map(|(i, s)| (s, i))
+        .collect()
+}
+
+// parse_forestは(森, 根)を返す式で，その森から木を組み立てるYaccを作る
+pub fn create_forest_parser(
+    start_symbol: &str,
+    nonterminal_symbols: &HashSet<String>,
+    map_lhs2items: &HashMap<String, HashSet<Item>>,
+    actions: &Actions,
+    symbol_map: &HashMap<&str, usize>,
+    parse_forest: &str,
+) -> String {
+    let mut ret = String::new();
+
+    ret.push_str(&static_rules(map_lhs2items, symbol_map));
+    ret.push_str(&fn_reduce_tree(map_lhs2items, actions));
+    ret.push_str(&enum_ambiguity(nonterminal_symbols));
+    ret.push_str(&impl_yacc(start_symbol, parse_forest));
+    ret.push_str(&impl_parser(start_symbol));
+
+    ret
+}
+
+fn static_rules(
+    map_lhs2items: &HashMap<String, HashSet<Item>>,
+    symbol_map: &HashMap<&str, usize>,
+) -> String {
+    let mut items: Vec<_> = map_lhs2items.values().flatten().collect();
+    items.sort();
+
+    let mut ret = String::new();
+
+    ret.push_str(
+        "
+static FOREST_RULES: &[forest::Rule] = &[",
+    );
+
+    for item in items {
+        let rhs = item.get_rhs().iter().fold("".to_string(), |mut s, symbol| {
+            s.push_str(&format!("{}, ", symbol_map[&symbol[..]]));
+            s
+        });
+
+        ret.push_str(&format!(
+            "
+    forest::Rule {{ lhs: {}, rhs: &[{}] }},",
+            symbol_map[item.get_lhs()],
+            rhs
+        ));
+    }
+
+    ret.push_str(
+        "
+];",
+    );
+
+    ret
+}
+
+fn enum_ambiguity(nonterminal_symbols: &HashSet<String>) -> String {
+    let mut ret = String::new();
+    let mut arms = String::new();
+
+    ret.push_str(
+        "
+#[allow(non_camel_case_types)]
+#[derive(Debug)]
+pub enum Ambiguity {",
+    );
+
+    for symbol in nonterminal_symbols {
+        if symbol == "S_" {
+            continue;
+        }
+
+        ret.push_str(&format!(
+            "
+    {}(Vec<{}>),",
+            symbol,
+            ebnf::get_value_type(symbol)
+        ));
+
+        arms.push_str(&format!(
+            "
+            Tree::{}(_) => Ambiguity::{}(
+                trees
+                    .iter()
+                    .map(|t| if let Tree::{}(x) = t {{ x.clone() }} else {{ unreachable!() }})
+                    .collect(),
+            ),",
+            symbol, symbol, symbol
+        ));
+    }
+
+    ret.push_str(&format!(
+        "
+}}
+
+impl Ambiguity {{
+    fn from(trees: &[Tree]) -> Self {{
+        match &trees[0] {{{}
+            _ => unreachable!(),
+        }}
+    }}
+}}",
+        arms
+    ));
+
+    ret
+}
+
+// 森とその根，入力の字句をまとめて公開する．木は求められたときに作る
+fn impl_yacc(start_symbol: &str, parse_forest: &str) -> String {
+    format!(
+        "
+pub struct ParseForest {{
+    leaves: Vec<Tree>,
+    forest: forest::Forest,
+    root: usize,
+}}
+
+impl ParseForest {{
+    pub fn is_ambiguous(&self) -> bool {{
+        self.forest.is_ambiguous(self.root)
+    }}
+
+    pub fn trees(&self) -> impl Iterator<Item = {0}> + '_ {{
+        self.forest
+            .trees(self.root, |i| self.leaves[i].clone(), reduce_tree)
+            .filter_map(|t| if let Tree::{0}(x) = t {{ Some(x) }} else {{ None }})
+    }}
+
+    pub fn tree_with(&self, choose: impl Fn(&Ambiguity) -> usize) -> Option<{0}> {{
+        match self.forest.tree(
+            self.root,
+            &|i| self.leaves[i].clone(),
+            &reduce_tree,
+            &|trees| choose(&Ambiguity::from(trees)),
+        ) {{
+            Some(Tree::{0}(x)) => Some(x),
+            // 閉路があると木は無数にある
+            _ => None,
+        }}
+    }}
+}}
+
+#[allow(clippy::ptr_arg)]
+impl Yacc {{
+    pub fn parse_forest(s: &str, tokens: &Vec<Token>) -> Result<ParseForest, error::ParseError> {{
+        let mut v: Vec<_> = tokens.iter().map(|x| Tree::from(x)).collect();
+        v.push(Tree::F_(()));
+
+        match {1} {{
+            Ok((forest, root)) => Ok(ParseForest {{
+                leaves: v,
+                forest,
+                root,
+            }}),
+            Err(i) => Err(Self::error(s, tokens, i, &[], None)),
+        }}
+    }}
+
+    pub fn parse(s: &str, v: &Vec<Token>) -> Result<{0}, error::ParseError> {{
+        let forest = Self::parse_forest(s, v)?;
+
+        if forest.is_ambiguous() {{
+            return Err(error::ParseError::Ambiguous);
+        }}
+
+        forest.tree_with(|_| 0).ok_or(error::ParseError::Ambiguous)
+    }}
+
+    pub fn parse_all(s: &str, v: &Vec<Token>) -> Result<Vec<{0}>, error::ParseError> {{
+        Ok(Self::parse_forest(s, v)?.trees().collect())
+    }}
+
+    pub fn parse_with(
+        s: &str,
+        v: &Vec<Token>,
+        choose: &dyn Fn(&Ambiguity) -> usize,
+    ) -> Result<{0}, error::ParseError> {{
+        Self::parse_forest(s, v)?
+            .tree_with(choose)
+            .ok_or(error::ParseError::Ambiguous)
+    }}
+}}",
+        start_symbol, parse_forest
+    )
+}
+
+fn impl_parser(start_symbol: &str) -> String {
+    format!(
+        "
+impl Parser {{
+    pub fn parse_forest(s: &str) -> Result<ParseForest, error::ParseError> {{
+        let v = Lex::tokenize(s)?;
+        Yacc::parse_forest(s, &v)
+    }}
+
+    pub fn parse_all(s: &str) -> Result<Vec<{0}>, error::ParseError> {{
+        let v = Lex::tokenize(s)?;
+        Yacc::parse_all(s, &v)
+    }}
+
+    pub fn parse_with(s: &str, choose: impl Fn(&Ambiguity) -> usize) -> Result<{0}, error::ParseError> {{
+        let v = Lex::tokenize(s)?;
+        Yacc::parse_with(s, &v, &choose)
+    }}
+}}",
+        start_symbol
+    )
+}
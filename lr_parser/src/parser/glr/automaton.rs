@@ -0,0 +1,132 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    action::Actions,
+    item::Item,
+    parser::{enum_tree, forest, impl_tree, Action},
+};
+
+use super::parsing_table;
+
+pub fn create_glr_parser(
+    start_symbol: &str,
+    terminal_symbols: &HashSet<String>,
+    nonterminal_symbols: &HashSet<String>,
+    map_lhs2items: &HashMap<String, HashSet<Item>>,
+    actions: &Actions,
+) -> String {
+    let parsing_table =
+        parsing_table::compute_glr_parsing_table(start_symbol, nonterminal_symbols, map_lhs2items);
+
+    let symbol_map = forest::compute_symbol_map(terminal_symbols, nonterminal_symbols);
+
+    let mut ret = String::new();
+
+    ret.push_str(&fn_actions(&parsing_table, terminal_symbols));
+    ret.push_str(&fn_goto(&parsing_table, nonterminal_symbols, &symbol_map));
+    ret.push_str(&forest::create_forest_parser(
+        start_symbol,
+        nonterminal_symbols,
+        map_lhs2items,
+        actions,
+        &symbol_map,
+        "glr::parse(&v, &glr_actions, &glr_goto, FOREST_RULES)",
+    ));
+    ret.push_str(&enum_tree(terminal_symbols, nonterminal_symbols));
+    ret.push_str(&impl_tree(terminal_symbols));
+
+    ret
+}
+
+fn fn_actions(
+    parsing_table: &HashMap<usize, HashMap<Option<String>, Vec<Action>>>,
+    terminal_symbols: &HashSet<String>,
+) -> String {
+    let mut ret = String::new();
+
+    ret.push_str(
+        "
+fn glr_actions(state: usize, lookahead: Option<&Tree>) -> Vec<glr::Action> {
+    match (state, lookahead) {",
+    );
+
+    for (from, map) in parsing_table {
+        for (lookahead, actions) in map {
+            let pattern = match lookahead {
+                Some(x) if terminal_symbols.contains(x) => format!("Some(Tree::{}(_))", x),
+                Some(_) => continue,
+                None => "None".to_string(),
+            };
+
+            let actions = actions.iter().fold("".to_string(), |mut s, action| {
+                match action {
+                    Action::Shift(to) => s.push_str(&format!("glr::Action::Shift({}), ", to)),
+                    Action::Reduce(item) => s.push_str(&format!(
+                        "glr::Action::Reduce({}), ",
+                        item.get_rule_number()
+                    )),
+                    Action::Accept => s.push_str("glr::Action::Accept, "),
+                    Action::Error => {}
+                }
+                s
+            });
+
+            ret.push_str(&format!(
+                "
+        ({}, {}) => vec![{}],",
+                from, pattern, actions
+            ));
+        }
+    }
+
+    ret.push_str(
+        "
+        _ => vec![],
+    }
+}",
+    );
+
+    ret
+}
+
+fn fn_goto(
+    parsing_table: &HashMap<usize, HashMap<Option<String>, Vec<Action>>>,
+    nonterminal_symbols: &HashSet<String>,
+    symbol_map: &HashMap<&str, usize>,
+) -> String {
+    let mut ret = String::new();
+
+    ret.push_str(
+        "
+fn glr_goto(state: usize, symbol: usize) -> Option<usize> {
+    match (state, symbol) {",
+    );
+
+    for (from, map) in parsing_table {
+        for (lookahead, actions) in map {
+            if let Some(x) = lookahead
+                .as_ref()
+                .filter(|x| nonterminal_symbols.contains(*x))
+            {
+                if let Some(Action::Shift(to)) = actions.first() {
+                    ret.push_str(&format!(
+                        "
+        ({}, {}) => Some({}),",
+                        from,
+                        symbol_map[&x[..]],
+                        to
+                    ));
+                }
+            }
+        }
+    }
+
+    ret.push_str(
+        "
+        _ => None,
+    }
+}",
+    );
+
+    ret
+}
@@ -0,0 +1,56 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    item::{Item, LR1Closure},
+    parser::{first_sets, lalr, lr0, Action},
+};
+
+// LALR(1)の表と同じ作り方で，衝突する動作も全て残す
+pub fn compute_glr_parsing_table(
+    start_symbol: &str,
+    nonterminal_symbols: &HashSet<String>,
+    map_lhs2items: &HashMap<String, HashSet<Item>>,
+) -> HashMap<usize, HashMap<Option<String>, Vec<Action>>> {
+    let (lr0_transition_map, closure_state_map, accept_state) =
+        lr0::transition_map::compute_lr0_transition_map(start_symbol, map_lhs2items);
+
+    let first_sets = first_sets::compute_first_sets(nonterminal_symbols, map_lhs2items);
+    let lookahead_map = lalr::lookahead_map::compute_lalr_lookahead_map(
+        &lr0_transition_map,
+        &closure_state_map,
+        map_lhs2items,
+        &first_sets,
+    );
+
+    let mut ret: HashMap<usize, HashMap<Option<String>, Vec<Action>>> = HashMap::new();
+
+    // shift
+    for (u, map) in lr0_transition_map {
+        let transitions_from_u = ret.entry(u).or_default();
+        for (c, v) in map {
+            transitions_from_u
+                .entry(Some(c.to_string()))
+                .or_insert(vec![])
+                .push(Action::Shift(v));
+        }
+    }
+
+    // reduce
+    for (state, kernel) in lookahead_map {
+        let map = ret.get_mut(&state).unwrap();
+        for (item, lookahead_set) in LR1Closure::from(kernel, map_lhs2items, &first_sets) {
+            if item.is_reducible() && item.get_lhs() != "S_" {
+                for lookahead_symbol in lookahead_set {
+                    map.entry(lookahead_symbol)
+                        .or_insert(vec![])
+                        .push(Action::Reduce(item.clone()));
+                }
+            }
+        }
+    }
+
+    //accept
+    ret.insert(accept_state, HashMap::from([(None, vec![Action::Accept])]));
+
+    ret
+}
@@ -0,0 +1,2 @@
+pub mod lookahead_map;
+pub mod parsing_table;
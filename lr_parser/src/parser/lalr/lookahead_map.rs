@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::item::{Item, LR0Closure, LR1Closure};
+
+// 伝播を調べるためのダミーの先読み記号（識別子には現れない）
+const DUMMY_LOOKAHEAD: &str = "#";
+
+// LR(0)オートマトンの各状態のカーネル項に，先読み記号の伝播によってLALR(1)の先読み集合を付ける
+pub fn compute_lalr_lookahead_map(
+    lr0_transition_map: &HashMap<usize, HashMap<String, usize>>,
+    closure_state_map: &HashMap<LR0Closure, usize>,
+    map_lhs2items: &HashMap<String, HashSet<Item>>, // lhs -> 左辺がlhsのitemの集合
+    first_sets: &HashMap<Vec<String>, HashSet<Option<String>>>,
+) -> HashMap<usize, BTreeMap<Item, BTreeSet<Option<String>>>> {
+    let dummy = Some(DUMMY_LOOKAHEAD.to_string());
+
+    // Map<状態, Map<カーネル項, 先読み記号の集合>>
+    let mut lookahead_map: HashMap<usize, BTreeMap<Item, BTreeSet<Option<String>>>> =
+        closure_state_map
+            .iter()
+            .map(|(closure, &state)| {
+                (
+                    state,
+                    closure
+                        .kernel()
+                        .map(|item| (item.clone(), BTreeSet::new()))
+                        .collect(),
+                )
+            })
+            .collect();
+
+    // Map<(状態, カーネル項), 先読み記号が伝播する先の(状態, カーネル項)>
+    let mut propagation_map: HashMap<(usize, Item), Vec<(usize, Item)>> = HashMap::new();
+
+    // 自然発生する先読み記号
+    let mut vdq = VecDeque::new();
+
+    for (closure, &state) in closure_state_map {
+        for kernel_item in closure.kernel() {
+            if kernel_item.get_lhs() == "S_" && !kernel_item.is_reducible() {
+                vdq.push_back((state, kernel_item.clone(), None));
+            }
+
+            let lr1_closure = LR1Closure::from(
+                [(kernel_item.clone(), BTreeSet::from([dummy.clone()]))],
+                map_lhs2items,
+                first_sets,
+            );
+
+            for (mut item, lookahead_set) in lr1_closure {
+                if let Some(x) = item.shift() {
+                    let next_state = lr0_transition_map[&state][&x];
+
+                    for lookahead_symbol in lookahead_set {
+                        if lookahead_symbol == dummy {
+                            propagation_map
+                                .entry((state, kernel_item.clone()))
+                                .or_default()
+                                .push((next_state, item.clone()));
+                        } else {
+                            vdq.push_back((next_state, item.clone(), lookahead_symbol));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    while let Some((state, item, lookahead_symbol)) = vdq.pop_front() {
+        let lookahead_set = lookahead_map
+            .get_mut(&state)
+            .unwrap()
+            .get_mut(&item)
+            .unwrap();
+
+        if lookahead_set.insert(lookahead_symbol.clone()) {
+            if let Some(v) = propagation_map.get(&(state, item)) {
+                for (next_state, next_item) in v {
+                    vdq.push_back((*next_state, next_item.clone(), lookahead_symbol.clone()));
+                }
+            }
+        }
+    }
+
+    lookahead_map
+}
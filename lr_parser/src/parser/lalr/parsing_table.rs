@@ -0,0 +1,81 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    conflict::Conflict,
+    item::{Item, LR1Closure},
+    parser::{counterexample, first_sets, insert_action, lr0, Action, ParsingTable},
+    precedence::Precedence,
+};
+
+use super::lookahead_map;
+
+pub fn compute_lalr_parsing_table(
+    start_symbol: &str,
+    nonterminal_symbols: &HashSet<String>,
+    map_lhs2items: &HashMap<String, HashSet<Item>>,
+    precedence: &Precedence,
+) -> (ParsingTable, Vec<Conflict>) {
+    let (lr0_transition_map, closure_state_map, accept_state) =
+        lr0::transition_map::compute_lr0_transition_map(start_symbol, map_lhs2items);
+
+    let first_sets = first_sets::compute_first_sets(nonterminal_symbols, map_lhs2items);
+    let lookahead_map = lookahead_map::compute_lalr_lookahead_map(
+        &lr0_transition_map,
+        &closure_state_map,
+        map_lhs2items,
+        &first_sets,
+    );
+
+    let mut ret = ParsingTable::new();
+    let mut conflicts = vec![];
+
+    // shift
+    for (&u, map) in &lr0_transition_map {
+        let transitions_from_u = ret.entry(u).or_default();
+        for (c, &v) in map {
+            transitions_from_u.insert(Some(c.to_string()), Action::Shift(v));
+        }
+    }
+
+    // reduce
+    for (state, kernel) in lookahead_map {
+        let map = ret.get_mut(&state).unwrap();
+        let closure: Vec<_> = LR1Closure::from(kernel, map_lhs2items, &first_sets)
+            .into_iter()
+            .collect();
+        let items: Vec<_> = closure.iter().map(|(item, _)| item.clone()).collect();
+        for (item, lookahead_set) in closure {
+            if item.is_reducible() && item.get_lhs() != "S_" {
+                for lookahead_symbol in lookahead_set {
+                    insert_action(
+                        map,
+                        state,
+                        &items,
+                        lookahead_symbol,
+                        Action::Reduce(item.clone()),
+                        precedence,
+                        &mut conflicts,
+                    );
+                }
+            }
+        }
+    }
+
+    //accept
+    ret.insert(accept_state, HashMap::from([(None, Action::Accept)]));
+
+    if !conflicts.is_empty() {
+        let state_items = closure_state_map
+            .iter()
+            .map(|(closure, &state)| (state, closure.into_iter().cloned().collect()))
+            .collect();
+        counterexample::attach_counterexamples(
+            &mut conflicts,
+            &lr0_transition_map,
+            &state_items,
+            &first_sets,
+        );
+    }
+
+    (ret, conflicts)
+}
@@ -0,0 +1,2 @@
+pub mod prediction_table;
+pub mod table_driven;
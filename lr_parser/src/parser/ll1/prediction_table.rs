@@ -0,0 +1,100 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    error::{GrammarError, Location},
+    item::Item,
+    parser::{first_sets, follow_sets},
+};
+
+// Map<非終端記号, Map<先読み記号, 適用する規則>>
+pub fn compute_ll1_prediction_table(
+    nonterminal_symbols: &HashSet<String>,
+    map_lhs2items: &HashMap<String, HashSet<Item>>,
+) -> Result<HashMap<String, HashMap<String, Item>>, Vec<GrammarError>> {
+    let first_sets = first_sets::compute_first_sets(nonterminal_symbols, map_lhs2items);
+    let follow_sets =
+        follow_sets::compute_follow_sets(nonterminal_symbols, map_lhs2items, &first_sets);
+
+    let mut ret: HashMap<String, HashMap<String, Item>> = HashMap::new();
+    let mut errors = vec![];
+
+    // 誤りを毎回同じ順に報告する
+    let mut lhs_items: Vec<_> = map_lhs2items.iter().collect();
+    lhs_items.sort_by_key(|(lhs, _)| *lhs);
+
+    for (lhs, item_set) in lhs_items {
+        if lhs == "S_" {
+            continue;
+        }
+
+        let map = ret.entry(lhs.to_string()).or_default();
+
+        let mut items: Vec<_> = item_set.iter().collect();
+        items.sort();
+
+        // FIRSTで決まる先読み記号を先に埋め，FOLLOWで決まるものは後から埋める
+        let mut nullable_items = vec![];
+
+        for item in items {
+            for symbol in first_sets.get(item.get_rhs()).unwrap() {
+                if let Some(symbol) = symbol {
+                    if let Some(other) = map.insert(symbol.to_string(), item.clone()) {
+                        errors.push(GrammarError {
+                            location: Location::Rule(item.get_rule_number()),
+                            message: format!(
+                                "FIRST/FIRST conflict on {}: {:?} and {:?}",
+                                symbol, other, item
+                            ),
+                        });
+                    }
+                } else {
+                    nullable_items.push(item);
+                }
+            }
+        }
+
+        for item in nullable_items {
+            let mut follow_set: Vec<_> = follow_sets.get(lhs).unwrap().iter().collect();
+            follow_set.sort();
+
+            // 空列を導出する規則どうしの衝突は，FOLLOWの記号ごとではなく一度だけ報告する
+            let mut nullable_others = vec![];
+
+            for symbol in follow_set {
+                let Some(other) = map.insert(symbol.to_string(), item.clone()) else {
+                    continue;
+                };
+
+                let lookahead = Some(symbol.to_string());
+                if first_sets
+                    .get(other.get_rhs())
+                    .unwrap()
+                    .contains(&lookahead)
+                {
+                    errors.push(GrammarError {
+                        location: Location::Rule(item.get_rule_number()),
+                        message: format!(
+                            "FIRST/FOLLOW conflict on {}: {:?} and {:?}",
+                            symbol, other, item
+                        ),
+                    });
+                } else if !nullable_others.contains(&other) {
+                    errors.push(GrammarError {
+                        location: Location::Rule(item.get_rule_number()),
+                        message: format!(
+                            "FIRST/FIRST conflict on the empty string: {:?} and {:?}",
+                            other, item
+                        ),
+                    });
+                    nullable_others.push(other);
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(ret)
+    } else {
+        Err(errors)
+    }
+}
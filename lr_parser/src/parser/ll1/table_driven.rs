@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::action::Actions;
+use crate::error::GrammarError;
+use crate::item::Item;
+use crate::parser::{create_stub, enum_tree, fn_reduce_tree, forest, impl_tree};
+
+use super::prediction_table;
+
+pub fn create_ll1_parser(
+    start_symbol: &str,
+    terminal_symbols: &HashSet<String>,
+    nonterminal_symbols: &HashSet<String>,
+    map_lhs2items: &HashMap<String, HashSet<Item>>,
+    actions: &Actions,
+    lazy: bool,
+) -> (String, Vec<GrammarError>) {
+    let mut ret = String::new();
+
+    match prediction_table::compute_ll1_prediction_table(nonterminal_symbols, map_lhs2items) {
+        Ok(prediction_table) => {
+            let symbol_map = forest::compute_symbol_map(terminal_symbols, nonterminal_symbols);
+
+            ret.push_str(&enum_ll1());
+            ret.push_str(&fn_shift(terminal_symbols, &symbol_map));
+            ret.push_str(&fn_predict(&prediction_table, &symbol_map));
+            ret.push_str(&fn_rhs(terminal_symbols, map_lhs2items, &symbol_map));
+            ret.push_str(&fn_reduce_tree(map_lhs2items, actions));
+            ret.push_str(&impl_yacc(start_symbol, symbol_map[start_symbol]));
+            ret.push_str(&enum_tree(terminal_symbols, nonterminal_symbols));
+            ret.push_str(&impl_tree(terminal_symbols));
+
+            (ret, vec![])
+        }
+
+        Err(errors) => create_stub(start_symbol, errors, lazy, false),
+    }
+}
+
+// 再帰せずにスタックで予測表を引くので，入力が深くてもスタックは溢れない
+fn enum_ll1() -> String {
+    "
+#[derive(Clone, Copy)]
+enum Ll1 {
+    // 番号の字句を読む
+    Shift(usize),
+    // 番号の非終端記号を先読みで規則に展開する
+    Predict(usize),
+    // 番号の規則の右辺を読み終えたので，子から左辺の値を作る
+    Reduce(usize),
+}"
+    .to_string()
+}
+
+fn fn_shift(terminal_symbols: &HashSet<String>, symbol_map: &HashMap<&str, usize>) -> String {
+    let mut symbols: Vec<_> = terminal_symbols.iter().filter(|&x| x != "F_").collect();
+    symbols.sort();
+
+    let mut arms = String::new();
+
+    for symbol in symbols {
+        arms.push_str(&format!(
+            "
+        ({0}, Some(t @ Token::{1}(_))) => Ok(Tree::from(t)),
+        ({0}, _) => Err(&[\"{1}\"]),",
+            symbol_map[&symbol[..]],
+            symbol
+        ));
+    }
+
+    format!(
+        "
+fn ll1_shift(x: usize, t: Option<&Token>) -> Result<Tree, &'static [&'static str]> {{
+    match (x, t) {{{}
+        _ => unreachable!(),
+    }}
+}}",
+        arms
+    )
+}
+
+fn fn_predict(
+    prediction_table: &HashMap<String, HashMap<String, Item>>,
+    symbol_map: &HashMap<&str, usize>,
+) -> String {
+    let mut symbols: Vec<_> = prediction_table.keys().collect();
+    symbols.sort();
+
+    let mut arms = String::new();
+
+    for symbol in symbols {
+        let map = &prediction_table[symbol];
+
+        // 同じ規則を適用する先読み記号をまとめる
+        let mut item_lookaheads_map: BTreeMap<&Item, Vec<&str>> = BTreeMap::new();
+        for (lookahead_symbol, item) in map {
+            item_lookaheads_map
+                .entry(item)
+                .or_default()
+                .push(lookahead_symbol);
+        }
+
+        for (item, mut lookahead_symbols) in item_lookaheads_map {
+            lookahead_symbols.sort();
+
+            let pattern = lookahead_symbols
+                .iter()
+                .map(|&x| {
+                    if x == "F_" {
+                        "None".to_string()
+                    } else {
+                        format!("Some(Token::{}(_))", x)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" | ");
+
+            arms.push_str(&format!(
+                "
+        ({}, {}) => Ok({}),",
+                symbol_map[&symbol[..]],
+                pattern,
+                item.get_rule_number()
+            ));
+        }
+
+        // 先読みできる記号
+        let mut expected: Vec<_> = map.keys().map(|x| format!("{:?}", x)).collect();
+        expected.sort();
+
+        arms.push_str(&format!(
+            "
+        ({}, _) => Err(&[{}]),",
+            symbol_map[&symbol[..]],
+            expected.join(", ")
+        ));
+    }
+
+    format!(
+        "
+fn ll1_predict(x: usize, t: Option<&Token>) -> Result<usize, &'static [&'static str]> {{
+    match (x, t) {{{}
+        _ => unreachable!(),
+    }}
+}}",
+        arms
+    )
+}
+
+fn fn_rhs(
+    terminal_symbols: &HashSet<String>,
+    map_lhs2items: &HashMap<String, HashSet<Item>>,
+    symbol_map: &HashMap<&str, usize>,
+) -> String {
+    let mut items: Vec<_> = map_lhs2items.values().flatten().collect();
+    items.sort();
+
+    let mut arms = String::new();
+
+    for item in items {
+        if item.get_lhs() == "S_" {
+            continue;
+        }
+
+        let rhs = item
+            .get_rhs()
+            .iter()
+            .map(|x| {
+                if terminal_symbols.contains(x) {
+                    format!("Ll1::Shift({})", symbol_map[&x[..]])
+                } else {
+                    format!("Ll1::Predict({})", symbol_map[&x[..]])
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        arms.push_str(&format!(
+            "
+        {} => &[{}],",
+            item.get_rule_number(),
+            rhs
+        ));
+    }
+
+    format!(
+        "
+fn ll1_rhs(rule: usize) -> &'static [Ll1] {{
+    match rule {{{}
+        _ => unreachable!(),
+    }}
+}}",
+        arms
+    )
+}
+
+fn impl_yacc(start_symbol: &str, start: usize) -> String {
+    format!(
+        "
+#[allow(clippy::ptr_arg)]
+impl Yacc {{
+    pub fn parse(s: &str, v: &Vec<Token>) -> Result<{0}, error::ParseError> {{
+        let mut idx = 0;
+        let mut stack = vec![Ll1::Predict({1})];
+        let mut values = vec![];
+
+        while let Some(x) = stack.pop() {{
+            match x {{
+                Ll1::Shift(x) => {{
+                    let t = ll1_shift(x, v.get(idx))
+                        .map_err(|expected| Self::error(s, v, idx, expected, None))?;
+                    values.push(t);
+                    idx += 1;
+                }}
+                Ll1::Predict(x) => {{
+                    let rule = ll1_predict(x, v.get(idx))
+                        .map_err(|expected| Self::error(s, v, idx, expected, None))?;
+                    // 子は左から順に読む
+                    stack.push(Ll1::Reduce(rule));
+                    stack.extend(ll1_rhs(rule).iter().rev());
+                }}
+                Ll1::Reduce(rule) => {{
+                    let children = values.split_off(values.len() - ll1_rhs(rule).len());
+                    values.push(reduce_tree(rule, children));
+                }}
+            }}
+        }}
+
+        if idx != v.len() {{
+            return Err(Self::error(s, v, idx, &[\"F_\"], None));
+        }}
+
+        match values.pop() {{
+            Some(Tree::{0}(x)) => Ok(x),
+            _ => unreachable!(),
+        }}
+    }}
+}}",
+        start_symbol, start
+    )
+}
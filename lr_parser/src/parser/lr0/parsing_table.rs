@@ -1,40 +1,54 @@
 use std::collections::{HashMap, HashSet};
 
-use crate::{item::Item, parser::Action};
+use crate::{
+    conflict::Conflict,
+    item::Item,
+    parser::{counterexample, first_sets, insert_action, Action, ParsingTable},
+    precedence::Precedence,
+};
 
 use super::transition_map;
 
 pub fn compute_lr0_parsing_table(
     start_symbol: &str,
     terminal_symbols: &HashSet<String>,
+    nonterminal_symbols: &HashSet<String>,
     map_lhs2items: &HashMap<String, HashSet<Item>>,
-) -> Result<HashMap<usize, HashMap<Option<String>, Action>>, String> {
+    precedence: &Precedence,
+) -> (ParsingTable, Vec<Conflict>) {
     let (lr0_transition_map, closure_state_map, accept_state) =
         transition_map::compute_lr0_transition_map(start_symbol, map_lhs2items);
 
-    let mut ret: HashMap<usize, HashMap<Option<String>, Action>> = HashMap::new();
+    let first_sets = first_sets::compute_first_sets(nonterminal_symbols, map_lhs2items);
+
+    let mut ret = ParsingTable::new();
+    let mut conflicts = vec![];
 
     // shift
-    for (u, map) in lr0_transition_map {
+    for (&u, map) in &lr0_transition_map {
         let transitions_from_u = ret.entry(u).or_insert(HashMap::new());
-        for (c, v) in map {
+        for (c, &v) in map {
             transitions_from_u.insert(Some(c.to_string()), Action::Shift(v));
         }
     }
 
     // reduce
     for (closure, &state) in &closure_state_map {
-        if let Some(reducible_item) = closure.into_iter().find(|&item| item.is_reducible()) {
-            if closure.len() == 1 {
-                let map = ret.get_mut(&state).unwrap();
+        let map = ret.get_mut(&state).unwrap();
+        let items: Vec<_> = closure.into_iter().cloned().collect();
+        for item in &items {
+            if item.is_reducible() && item.get_lhs() != "S_" {
                 for symbol in terminal_symbols {
-                    map.insert(
+                    insert_action(
+                        map,
+                        state,
+                        &items,
                         Some(symbol.to_string()),
-                        Action::Reduce(reducible_item.clone()),
+                        Action::Reduce(item.clone()),
+                        precedence,
+                        &mut conflicts,
                     );
                 }
-            } else {
-                return Err("shift/reduce or reduce/reduce conflict!".to_string());
             }
         }
     }
@@ -42,5 +56,18 @@ pub fn compute_lr0_parsing_table(
     //accept
     ret.insert(accept_state, HashMap::from([(None, Action::Accept)]));
 
-    Ok(ret)
+    if !conflicts.is_empty() {
+        let state_items = closure_state_map
+            .iter()
+            .map(|(closure, &state)| (state, closure.into_iter().cloned().collect()))
+            .collect();
+        counterexample::attach_counterexamples(
+            &mut conflicts,
+            &lr0_transition_map,
+            &state_items,
+            &first_sets,
+        );
+    }
+
+    (ret, conflicts)
 }
@@ -1,4 +1,4 @@
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use crate::item::{Item, LR0Closure};
 
@@ -54,8 +54,8 @@ fn rec(
     closure_state_map.insert(closure.clone(), closure_num);
     parsing_table.insert(closure_num, HashMap::new());
 
-    // 読む文字と次のitemのvdqのマップ
-    let mut nexts = HashMap::new();
+    // 読む文字と次のitemのvdqのマップ．状態の番号が毎回同じになるよう文字の順に辿る
+    let mut nexts = BTreeMap::new();
 
     for item in &closure {
         if let Some(x) = item.get_symbol_under_pointer() {
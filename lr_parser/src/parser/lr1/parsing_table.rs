@@ -1,15 +1,18 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use crate::{
+    conflict::Conflict,
     item::{Item, LR1Closure},
-    parser::{first_sets, Action},
+    parser::{counterexample, first_sets, insert_action, Action, ParsingTable},
+    precedence::Precedence,
 };
 
 pub fn compute_lr1_parsing_table(
     start_symbol: &str,
     nonterminal_symbols: &HashSet<String>,
     map_lhs2items: &HashMap<String, HashSet<Item>>, // lhs -> 左辺がlhsであり，かつポインタが左端にあるitemの集合
-) -> Result<HashMap<usize, HashMap<Option<String>, Action>>, String> {
+    precedence: &Precedence,
+) -> (ParsingTable, Vec<Conflict>) {
     let first_sets = &first_sets::compute_first_sets(nonterminal_symbols, map_lhs2items);
 
     let initial_item = Item::from(
@@ -36,43 +39,66 @@ pub fn compute_lr1_parsing_table(
         first_sets,
     );
 
-    let mut parsing_table = HashMap::new();
-    let mut closure_state_map = HashMap::new();
+    let mut table = Table::default();
     rec(
         initial_closure,
-        &map_lhs2items,
-        &mut parsing_table,
-        &mut closure_state_map,
+        map_lhs2items,
         first_sets,
-    )?;
+        precedence,
+        &mut table,
+    );
 
-    let &accept_state = closure_state_map.get(&accept_closure).unwrap();
+    let &accept_state = table.closure_state_map.get(&accept_closure).unwrap();
 
-    parsing_table
+    table
+        .parsing_table
         .get_mut(&accept_state)
         .unwrap()
         .insert(None, Action::Accept);
 
-    Ok(parsing_table)
+    if !table.conflicts.is_empty() {
+        counterexample::attach_counterexamples(
+            &mut table.conflicts,
+            &table.transition_map,
+            &table.state_items,
+            first_sets,
+        );
+    }
+
+    (table.parsing_table, table.conflicts)
+}
+
+// 構築中の表．反例を探すために，各状態からの遷移と各状態の項も記録する
+#[derive(Default)]
+struct Table {
+    parsing_table: ParsingTable,
+    closure_state_map: HashMap<LR1Closure, usize>,
+    transition_map: HashMap<usize, HashMap<String, usize>>,
+    state_items: HashMap<usize, Vec<Item>>,
+    conflicts: Vec<Conflict>,
 }
 
 fn rec(
     closure: LR1Closure,
     map_lhs2items: &HashMap<String, HashSet<Item>>, // lhs -> 左辺がlhsのitemの集合
-    parsing_table: &mut HashMap<usize, HashMap<Option<String>, Action>>,
-    closure_state_map: &mut HashMap<LR1Closure, usize>,
     first_sets: &HashMap<Vec<String>, HashSet<Option<String>>>,
-) -> Result<usize, String> {
-    if let Some(&closure_num) = closure_state_map.get(&closure) {
-        return Ok(closure_num);
+    precedence: &Precedence,
+    table: &mut Table,
+) -> usize {
+    if let Some(&closure_num) = table.closure_state_map.get(&closure) {
+        return closure_num;
     }
 
-    let closure_num = closure_state_map.len();
-    closure_state_map.insert(closure.clone(), closure_num);
-    parsing_table.insert(closure_num, HashMap::new());
+    let closure_num = table.closure_state_map.len();
+    table.closure_state_map.insert(closure.clone(), closure_num);
+    table.parsing_table.insert(closure_num, HashMap::new());
+    table.transition_map.insert(closure_num, HashMap::new());
+
+    let items: Vec<_> = closure.clone().into_iter().map(|(item, _)| item).collect();
+    table.state_items.insert(closure_num, items.clone());
 
-    // Map<読む文字, 次のclosureに含まれる(item,先読み文字の集合)>
-    let mut nexts = HashMap::new();
+    // Map<読む文字, 次のclosureに含まれる(item,先読み文字の集合)>．状態の番号が毎回同じになるよう文字の順に辿る
+    let mut nexts = BTreeMap::new();
 
     for (mut item, lookahead_set) in closure.clone() {
         if let Some(x) = item.shift() {
@@ -87,34 +113,37 @@ fn rec(
             // 還元項の場合
             let reducible_item = item;
             for lookahead_symbol in lookahead_set {
-                if let Some(_) = parsing_table
-                    .get_mut(&closure_num)
-                    .unwrap()
-                    .insert(lookahead_symbol, Action::Reduce(reducible_item.clone()))
-                {
-                    return Err("reduce/reduce conflict!".to_string());
-                }
+                insert_action(
+                    table.parsing_table.get_mut(&closure_num).unwrap(),
+                    closure_num,
+                    &items,
+                    lookahead_symbol,
+                    Action::Reduce(reducible_item.clone()),
+                    precedence,
+                    &mut table.conflicts,
+                );
             }
         }
     }
 
     for (c, v) in nexts {
         let next_closure = LR1Closure::from(v, map_lhs2items, first_sets);
-        let next_closure_num = rec(
-            next_closure,
-            map_lhs2items,
-            parsing_table,
-            closure_state_map,
-            first_sets,
-        )?;
-        if let Some(_) = parsing_table
+        let next_closure_num = rec(next_closure, map_lhs2items, first_sets, precedence, table);
+        table
+            .transition_map
             .get_mut(&closure_num)
             .unwrap()
-            .insert(Some(c), Action::Shift(next_closure_num))
-        {
-            // return Err("shift/reduce conflict!".to_string());
-        }
+            .insert(c.clone(), next_closure_num);
+        insert_action(
+            table.parsing_table.get_mut(&closure_num).unwrap(),
+            closure_num,
+            &items,
+            Some(c),
+            Action::Shift(next_closure_num),
+            precedence,
+            &mut table.conflicts,
+        );
     }
 
-    Ok(closure_num)
+    closure_num
 }
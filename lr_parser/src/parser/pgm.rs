@@ -0,0 +1 @@
+pub mod parsing_table;
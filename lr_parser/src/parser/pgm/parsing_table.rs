@@ -0,0 +1,210 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::{
+    conflict::Conflict,
+    item::{Item, LR1Closure},
+    parser::{counterexample, first_sets, insert_action, Action, ParsingTable},
+    precedence::Precedence,
+};
+
+type Kernel = BTreeMap<Item, BTreeSet<Option<String>>>;
+
+// Pagerの弱両立性(weak compatibility)によって，衝突を増やさない範囲でLR(1)の状態を併合する
+pub fn compute_pgm_parsing_table(
+    start_symbol: &str,
+    nonterminal_symbols: &HashSet<String>,
+    map_lhs2items: &HashMap<String, HashSet<Item>>, // lhs -> 左辺がlhsであり，かつポインタが左端にあるitemの集合
+    precedence: &Precedence,
+) -> (ParsingTable, Vec<Conflict>) {
+    let first_sets = &first_sets::compute_first_sets(nonterminal_symbols, map_lhs2items);
+
+    let initial_item = Item::from(
+        (0, "S_0".to_string()),
+        "S_".to_string(),
+        vec![start_symbol.to_string(), "F_".to_string()],
+        0,
+    );
+    let accept_item = Item::from(
+        (0, "S_0".to_string()),
+        "S_".to_string(),
+        vec![start_symbol.to_string(), "F_".to_string()],
+        2,
+    );
+
+    let (kernels, transition_map) =
+        compute_pgm_transition_map(initial_item, map_lhs2items, first_sets);
+
+    // 状態0から到達可能な状態だけを残して番号を振り直す
+    let state_map = {
+        let mut tmp = HashMap::from([(0, 0)]);
+        let mut vdq = VecDeque::from([0]);
+
+        while let Some(u) = vdq.pop_front() {
+            for &v in transition_map[u].values() {
+                if !tmp.contains_key(&v) {
+                    tmp.insert(v, tmp.len());
+                    vdq.push_back(v);
+                }
+            }
+        }
+
+        tmp
+    };
+
+    let mut parsing_table = ParsingTable::new();
+    let mut conflicts = vec![];
+    let mut accept_state = None;
+
+    // 反例を探すための，振り直した番号での遷移と項
+    let mut new_transition_map = HashMap::new();
+    let mut state_items = HashMap::new();
+
+    for (&u, &new_u) in &state_map {
+        let map = parsing_table.entry(new_u).or_default();
+
+        // shift
+        for (c, v) in &transition_map[u] {
+            map.insert(Some(c.to_string()), Action::Shift(state_map[v]));
+        }
+        new_transition_map.insert(
+            new_u,
+            transition_map[u]
+                .iter()
+                .map(|(c, v)| (c.clone(), state_map[v]))
+                .collect(),
+        );
+
+        if kernels[u].contains_key(&accept_item) {
+            accept_state = Some(new_u);
+        }
+
+        // reduce
+        let closure: Vec<_> = LR1Closure::from(kernels[u].clone(), map_lhs2items, first_sets)
+            .into_iter()
+            .collect();
+        let items: Vec<_> = closure.iter().map(|(item, _)| item.clone()).collect();
+        for (item, lookahead_set) in closure {
+            if item.is_reducible() && item.get_lhs() != "S_" {
+                for lookahead_symbol in lookahead_set {
+                    insert_action(
+                        map,
+                        new_u,
+                        &items,
+                        lookahead_symbol,
+                        Action::Reduce(item.clone()),
+                        precedence,
+                        &mut conflicts,
+                    );
+                }
+            }
+        }
+        state_items.insert(new_u, items);
+    }
+
+    if !conflicts.is_empty() {
+        counterexample::attach_counterexamples(
+            &mut conflicts,
+            &new_transition_map,
+            &state_items,
+            first_sets,
+        );
+    }
+
+    //accept
+    parsing_table.insert(
+        accept_state.unwrap(),
+        HashMap::from([(None, Action::Accept)]),
+    );
+
+    (parsing_table, conflicts)
+}
+
+fn compute_pgm_transition_map(
+    initial_item: Item,
+    map_lhs2items: &HashMap<String, HashSet<Item>>, // lhs -> 左辺がlhsのitemの集合
+    first_sets: &HashMap<Vec<String>, HashSet<Option<String>>>,
+) -> (Vec<Kernel>, Vec<BTreeMap<String, usize>>) {
+    let mut kernels = vec![Kernel::from([(initial_item, BTreeSet::from([None]))])];
+    let mut transition_map = vec![BTreeMap::new()];
+
+    // Map<カーネル項の集合(コア), そのコアを持つ状態のリスト>
+    let mut core_state_map: HashMap<BTreeSet<Item>, Vec<usize>> =
+        HashMap::from([(kernels[0].keys().cloned().collect(), vec![0])]);
+
+    let mut vdq = VecDeque::from([0]);
+
+    while let Some(u) = vdq.pop_front() {
+        // Map<読む文字, 次の状態のカーネル>．状態の番号が毎回同じになるよう文字の順に辿る
+        let mut nexts = BTreeMap::new();
+
+        for (mut item, lookahead_set) in
+            LR1Closure::from(kernels[u].clone(), map_lhs2items, first_sets)
+        {
+            if let Some(x) = item.shift() {
+                nexts
+                    .entry(x)
+                    .or_insert(Kernel::new())
+                    .insert(item, lookahead_set);
+            }
+        }
+
+        for (c, next_kernel) in nexts {
+            let core: BTreeSet<Item> = next_kernel.keys().cloned().collect();
+            let candidates = core_state_map.entry(core).or_insert(vec![]);
+
+            let v = if let Some(&v) = candidates
+                .iter()
+                .find(|&&v| is_weakly_compatible(&kernels[v], &next_kernel))
+            {
+                // 併合して先読み集合が増えたなら，vからの遷移をやり直す
+                let mut changed = false;
+                for (item, lookahead_set) in next_kernel {
+                    let old_set = kernels[v].get_mut(&item).unwrap();
+                    for lookahead_symbol in lookahead_set {
+                        changed |= old_set.insert(lookahead_symbol);
+                    }
+                }
+                if changed && !vdq.contains(&v) {
+                    vdq.push_back(v);
+                }
+                v
+            } else {
+                let v = kernels.len();
+                candidates.push(v);
+                kernels.push(next_kernel);
+                transition_map.push(BTreeMap::new());
+                vdq.push_back(v);
+                v
+            };
+
+            transition_map[u].insert(c, v);
+        }
+    }
+
+    (kernels, transition_map)
+}
+
+// 同じコアを持つ2つのカーネルについて，任意の異なる項i, jで
+// (L_i ∩ L'_j = ∅ かつ L'_i ∩ L_j = ∅) または L_i ∩ L_j ≠ ∅ または L'_i ∩ L'_j ≠ ∅
+// が成り立つなら，併合しても新たなreduce/reduce衝突は生じない
+fn is_weakly_compatible(kernel1: &Kernel, kernel2: &Kernel) -> bool {
+    let v: Vec<_> = kernel1.values().zip(kernel2.values()).collect();
+
+    for i in 0..v.len() {
+        for j in i + 1..v.len() {
+            let (l1_i, l2_i) = v[i];
+            let (l1_j, l2_j) = v[j];
+
+            if (l1_i.is_disjoint(l2_j) && l2_i.is_disjoint(l1_j))
+                || !l1_i.is_disjoint(l1_j)
+                || !l2_i.is_disjoint(l2_j)
+            {
+                continue;
+            }
+
+            return false;
+        }
+    }
+
+    true
+}
@@ -1,15 +1,18 @@
 use std::collections::{HashMap, HashSet};
 
 use crate::{
+    conflict::Conflict,
     item::Item,
-    parser::{first_sets, follow_sets, lr0, Action},
+    parser::{counterexample, first_sets, follow_sets, insert_action, lr0, Action, ParsingTable},
+    precedence::Precedence,
 };
 
 pub fn compute_slr_parsing_table(
     start_symbol: &str,
     nonterminal_symbols: &HashSet<String>,
     map_lhs2items: &HashMap<String, HashSet<Item>>,
-) -> Result<HashMap<usize, HashMap<Option<String>, Action>>, String> {
+    precedence: &Precedence,
+) -> (ParsingTable, Vec<Conflict>) {
     let (lr0_transition_map, closure_state_map, accept_state) =
         lr0::transition_map::compute_lr0_transition_map(start_symbol, map_lhs2items);
 
@@ -17,12 +20,13 @@ pub fn compute_slr_parsing_table(
     let follow_sets =
         follow_sets::compute_follow_sets(nonterminal_symbols, map_lhs2items, &first_sets);
 
-    let mut ret: HashMap<usize, HashMap<Option<String>, Action>> = HashMap::new();
+    let mut ret = ParsingTable::new();
+    let mut conflicts = vec![];
 
     // shift
-    for (u, map) in lr0_transition_map {
+    for (&u, map) in &lr0_transition_map {
         let transitions_from_u = ret.entry(u).or_insert(HashMap::new());
-        for (c, v) in map {
+        for (c, &v) in map {
             transitions_from_u.insert(Some(c.to_string()), Action::Shift(v));
         }
     }
@@ -30,14 +34,19 @@ pub fn compute_slr_parsing_table(
     // reduce
     for (closure, &state) in &closure_state_map {
         let map = ret.get_mut(&state).unwrap();
-        for item in closure {
+        let items: Vec<_> = closure.into_iter().cloned().collect();
+        for item in &items {
             if item.is_reducible() {
                 for symbol in follow_sets.get(item.get_lhs()).unwrap() {
-                    if let Some(_) =
-                        map.insert(Some(symbol.to_string()), Action::Reduce(item.clone()))
-                    {
-                        return Err("shift/reduce or reduce/reduce conflict!".to_string());
-                    }
+                    insert_action(
+                        map,
+                        state,
+                        &items,
+                        Some(symbol.to_string()),
+                        Action::Reduce(item.clone()),
+                        precedence,
+                        &mut conflicts,
+                    );
                 }
             }
         }
@@ -46,5 +55,18 @@ pub fn compute_slr_parsing_table(
     //accept
     ret.insert(accept_state, HashMap::from([(None, Action::Accept)]));
 
-    Ok(ret)
+    if !conflicts.is_empty() {
+        let state_items = closure_state_map
+            .iter()
+            .map(|(closure, &state)| (state, closure.into_iter().cloned().collect()))
+            .collect();
+        counterexample::attach_counterexamples(
+            &mut conflicts,
+            &lr0_transition_map,
+            &state_items,
+            &first_sets,
+        );
+    }
+
+    (ret, conflicts)
 }
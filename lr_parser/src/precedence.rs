@@ -0,0 +1,124 @@
+use proc_macro::{Span, TokenTree, TokenTree::*};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use crate::common::get_ident_string;
+use crate::item::Item;
+
+#[derive(Clone, Copy)]
+enum Assoc {
+    Left,
+    Right,
+    Nonassoc,
+}
+
+// shift/reduce衝突の解決結果
+pub enum Resolution {
+    Shift,
+    Reduce,
+    Error,
+}
+
+// 記号 -> (優先順位, 結合性)．後に宣言したものほど優先順位が高い
+type Levels = HashMap<String, (usize, Assoc)>;
+
+pub struct Precedence {
+    symbol_levels: Levels,
+    // 規則番号 -> 規則の優先順位を与える記号
+    rule_symbols: HashMap<usize, String>,
+    // 宣言の先頭の位置．PRECEDENCEがなければNone
+    span: Option<Span>,
+}
+
+impl Precedence {
+    // 規則の優先順位は%precで指定した記号，なければ右辺の最も右にある終端記号のもの
+    pub fn from(
+        arg: Option<TokenTree>,
+        terminal_symbols: &HashSet<String>,
+        map_lhs2items: &HashMap<String, HashSet<Item>>,
+        prec_symbols: &HashMap<usize, String>,
+    ) -> Self {
+        let (symbol_levels, span) = parse_precedence(arg);
+
+        let mut rule_symbols = prec_symbols.clone();
+        for item in map_lhs2items.values().flatten() {
+            if let Some(symbol) = item
+                .get_rhs()
+                .iter()
+                .rev()
+                .find(|x| terminal_symbols.contains(*x))
+            {
+                rule_symbols
+                    .entry(item.get_rule_number())
+                    .or_insert(symbol.clone());
+            }
+        }
+
+        Self {
+            symbol_levels,
+            rule_symbols,
+            span,
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    pub fn has_precedence(&self, symbol: &str) -> bool {
+        self.symbol_levels.contains_key(symbol)
+    }
+
+    // 先読み記号lookaheadでのitemによる還元とシフトの衝突を解決する．
+    // どちらかに優先順位がなければNone
+    pub fn resolve(&self, item: &Item, lookahead: &str) -> Option<Resolution> {
+        let rule_symbol = self.rule_symbols.get(&item.get_rule_number())?;
+        let &(rule_level, _) = self.symbol_levels.get(rule_symbol)?;
+        let &(level, assoc) = self.symbol_levels.get(lookahead)?;
+
+        Some(match rule_level.cmp(&level) {
+            Ordering::Greater => Resolution::Reduce,
+            Ordering::Less => Resolution::Shift,
+            Ordering::Equal => match assoc {
+                Assoc::Left => Resolution::Reduce,
+                Assoc::Right => Resolution::Shift,
+                Assoc::Nonassoc => Resolution::Error,
+            },
+        })
+    }
+}
+
+fn parse_precedence(arg: Option<TokenTree>) -> (Levels, Option<Span>) {
+    if let Some(Group(grp)) = arg {
+        let mut ret = HashMap::new();
+        let mut span = None;
+
+        for (level, tt) in grp.stream().into_iter().enumerate() {
+            if let Group(grp) = tt {
+                let mut it = grp.stream().into_iter();
+
+                let tt = it.next();
+                span = span.or(tt.as_ref().map(|x| x.span()));
+                let assoc = match &get_ident_string(tt)[..] {
+                    "left" => Assoc::Left,
+                    "right" => Assoc::Right,
+                    "nonassoc" => Assoc::Nonassoc,
+                    s => panic!("The associativity must be left, right or nonassoc! ({})", s),
+                };
+
+                for tt in it {
+                    let symbol = get_ident_string(Some(tt));
+                    if ret.insert(symbol.clone(), (level, assoc)).is_some() {
+                        panic!("The precedence of {} is declared twice!", symbol);
+                    }
+                }
+            } else {
+                panic!()
+            }
+        }
+
+        (ret, span)
+    } else {
+        panic!()
+    }
+}
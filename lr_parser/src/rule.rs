@@ -1,15 +1,57 @@
-use proc_macro::{TokenTree, TokenTree::*};
-use std::collections::{HashMap, HashSet};
+use proc_macro::{Delimiter, Span, TokenStream, TokenTree, TokenTree::*};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
+use crate::action::{self, Actions};
 use crate::common::get_ident_string;
+use crate::ebnf;
 use crate::item::Item;
 
+// Map<補助記号, (最初に書かれた欄の位置, Vec<(規則名, 右辺)>)>
+type Helpers = BTreeMap<String, (Span, Vec<(&'static str, Vec<String>)>)>;
+
+// 規則番号ごとの，Itemに含まれない情報
+#[derive(Default)]
+pub struct RuleInfo {
+    // %precで指定した記号
+    pub prec_symbols: HashMap<usize, String>,
+    // %precで指定した記号の位置
+    pub prec_spans: HashMap<usize, Span>,
+    // 規則名の位置
+    pub spans: HashMap<usize, Span>,
+    // 右辺の記号の位置
+    pub rhs_spans: HashMap<usize, Vec<Span>>,
+    // 非終端記号 -> 左辺に書かれた位置
+    pub lhs_spans: HashMap<String, Span>,
+    // 値の型を宣言した非終端記号
+    pub typed_symbols: HashSet<String>,
+    // 意味動作の式．$Nは右辺のN番目の値に置き換えてある
+    pub actions: Actions,
+}
+
 pub fn parse_rule(
     start_symbol: &str,
     arg: Option<TokenTree>,
-) -> (HashSet<String>, HashMap<String, HashSet<Item>>) {
+) -> (HashSet<String>, HashMap<String, HashSet<Item>>, RuleInfo) {
     let mut cnt = 0;
-    let mut tmp = f0(arg, &mut cnt);
+    let mut info = RuleInfo::default();
+    let mut helpers = Helpers::new();
+    let mut tmp = f0(arg, &mut cnt, &mut info, &mut helpers);
+
+    // 補助記号の規則は利用者の規則の後に番号を付け，展開元の欄を指す
+    for (lhs, (span, rules)) in helpers {
+        info.lhs_spans.insert(lhs.clone(), span);
+
+        let v = rules
+            .into_iter()
+            .map(|(rule, rhs)| {
+                cnt += 1;
+                info.spans.insert(cnt, span);
+                info.rhs_spans.insert(cnt, vec![span; rhs.len()]);
+                ((cnt, rule.to_string()), rhs)
+            })
+            .collect();
+        tmp.insert(lhs, v);
+    }
 
     let mut ret1: HashSet<_> = tmp.iter().map(|(s, _)| s.clone()).collect();
     ret1.insert("S_".to_string());
@@ -23,49 +65,123 @@ pub fn parse_rule(
     );
     let ret2 = f4(tmp);
 
-    (ret1, ret2)
+    (ret1, ret2, info)
 }
 
 fn f0(
     arg: Option<TokenTree>,
     cnt: &mut usize,
+    info: &mut RuleInfo,
+    helpers: &mut Helpers,
 ) -> HashMap<String, Vec<((usize, String), Vec<String>)>> {
     if let Some(Group(grp)) = arg {
-        grp.stream().into_iter().map(|tt| f1(tt, cnt)).collect()
+        grp.stream()
+            .into_iter()
+            .map(|tt| f1(tt, cnt, info, helpers))
+            .collect()
     } else {
         panic!()
     }
 }
 
-fn f1(arg: TokenTree, cnt: &mut usize) -> (String, Vec<((usize, String), Vec<String>)>) {
+fn f1(
+    arg: TokenTree,
+    cnt: &mut usize,
+    info: &mut RuleInfo,
+    helpers: &mut Helpers,
+) -> (String, Vec<((usize, String), Vec<String>)>) {
     if let Group(grp) = arg {
-        let mut it = grp.stream().into_iter();
-        let left = get_ident_string(it.next());
-        let v = it.map(|tt| f2(tt, cnt)).collect();
+        let mut it = grp.stream().into_iter().peekable();
+        let tt = it.next();
+        if let Some(tt) = &tt {
+            info.lhs_spans.insert(tt.to_string(), tt.span());
+        }
+        let left = get_ident_string(tt);
+        // 型は[]に包まれて渡される
+        if matches!(it.peek(), Some(Group(grp)) if grp.delimiter() == Delimiter::Bracket) {
+            it.next();
+            info.typed_symbols.insert(left.clone());
+        }
+        let v = it.map(|tt| f2(tt, cnt, info, helpers)).collect();
         (left, v)
     } else {
         panic!()
     }
 }
 
-fn f2(arg: TokenTree, cnt: &mut usize) -> ((usize, String), Vec<String>) {
+fn f2(
+    arg: TokenTree,
+    cnt: &mut usize,
+    info: &mut RuleInfo,
+    helpers: &mut Helpers,
+) -> ((usize, String), Vec<String>) {
     if let Group(grp) = arg {
         let mut it = grp.stream().into_iter();
-        let rule = get_ident_string(it.next());
-        let v = f3(it.next());
+        let tt = it.next();
+        let span = tt.as_ref().map(|x| x.span());
+        let rule = get_ident_string(tt);
+        let (v, rhs_spans) = f3(it.next(), helpers);
         *cnt += 1;
+        if let Some(span) = span {
+            info.spans.insert(*cnt, span);
+        }
+        info.rhs_spans.insert(*cnt, rhs_spans);
+        for tt in it {
+            match tt {
+                Group(grp) if grp.delimiter() == Delimiter::Brace => {
+                    let action = action::replace_fields(TokenStream::from(Group(grp)), &|n| {
+                        if n >= v.len() {
+                            panic!("The index after $ is out of range! (${} in {})", n, rule);
+                        }
+                        ebnf::get_action_value(&v[n], n).parse().unwrap()
+                    });
+                    info.actions.insert(*cnt, action);
+                }
+                tt => {
+                    info.prec_spans.insert(*cnt, tt.span());
+                    info.prec_symbols.insert(*cnt, get_ident_string(Some(tt)));
+                }
+            }
+        }
         ((*cnt, rule), v)
     } else {
         panic!()
     }
 }
 
-fn f3(arg: Option<TokenTree>) -> Vec<String> {
+// 欄は記号か，省略記法 kind<args>
+fn f3(arg: Option<TokenTree>, helpers: &mut Helpers) -> (Vec<String>, Vec<Span>) {
     if let Some(Group(grp)) = arg {
-        grp.stream()
-            .into_iter()
-            .map(|tt| get_ident_string(Some(tt)))
-            .collect()
+        let mut ret = (vec![], vec![]);
+        let mut it = grp.stream().into_iter().peekable();
+
+        while let Some(tt) = it.next() {
+            let span = tt.span();
+            let mut symbol = get_ident_string(Some(tt));
+
+            if matches!(it.peek(), Some(Punct(p)) if p.as_char() == '<') {
+                it.next();
+
+                let mut args = vec![];
+                loop {
+                    match it.next() {
+                        Some(Punct(p)) if p.as_char() == '>' => break,
+                        tt => args.push(get_ident_string(tt)),
+                    }
+                }
+
+                let (helper, rules) = ebnf::expand(&symbol, &args);
+                for (lhs, v) in rules {
+                    helpers.entry(lhs).or_insert((span, v));
+                }
+                symbol = helper;
+            }
+
+            ret.0.push(symbol);
+            ret.1.push(span);
+        }
+
+        ret
     } else {
         panic!()
     }
@@ -0,0 +1,130 @@
+use proc_macro::{Delimiter, TokenStream, TokenTree, TokenTree::*};
+use std::collections::HashSet;
+
+use crate::common;
+
+// 木の非終端記号の位置は子の位置を覆う範囲．型を宣言した非終端記号の値は位置を持たないので飛ばす
+//
+// 入力: 非終端記号 [型]? { 規則名 ( 欄 ... ) ... } ...
+pub fn impl_spanned(input: TokenStream) -> String {
+    let tokens = input.into_iter().collect::<Vec<_>>();
+
+    let typed_symbols: HashSet<_> = tokens
+        .iter()
+        .zip(tokens.iter().skip(1))
+        .filter_map(|(x, y)| match (x, y) {
+            (Ident(id), Group(grp)) if grp.delimiter() == Delimiter::Bracket => {
+                Some(id.to_string())
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut ret = String::new();
+    let mut it = tokens.into_iter();
+
+    while let Some(tt) = it.next() {
+        let lhs = common::get_ident_string(Some(tt));
+        let mut next = it.next();
+        if typed_symbols.contains(&lhs) {
+            next = it.next();
+        }
+
+        let rules = if let Some(Group(grp)) = next {
+            grp.stream().into_iter().collect::<Vec<_>>()
+        } else {
+            panic!()
+        };
+
+        if !typed_symbols.contains(&lhs) {
+            ret += &impl_spanned_for(&lhs, &rules, &typed_symbols);
+        }
+    }
+
+    ret
+}
+
+fn impl_spanned_for(lhs: &str, rules: &[TokenTree], typed_symbols: &HashSet<String>) -> String {
+    let arms = rules
+        .chunks(2)
+        .map(|x| {
+            let name = x[0].to_string();
+            let fields = match &x[1] {
+                Group(grp) if grp.delimiter() == Delimiter::Parenthesis => get_fields(grp.stream()),
+                _ => panic!(),
+            };
+
+            let vars = (0..fields.len())
+                .map(|i| format!("t{}", i))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let spans = fields
+                .iter()
+                .enumerate()
+                .map(|(i, (kind, symbol))| {
+                    if typed_symbols.contains(symbol) {
+                        return "None".to_string();
+                    }
+                    match kind.as_deref() {
+                        None => format!("span::Spanned::span(&**t{})", i),
+                        Some("Option") => {
+                            format!("t{}.as_ref().and_then(|x| span::Spanned::span(&**x))", i)
+                        }
+                        Some(_) => {
+                            format!("span::Span::merge(t{}.iter().map(span::Spanned::span))", i)
+                        }
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            format!(
+                "{}::{}({}) => {{
+                    let spans: [Option<span::Span>; {}] = [{}];
+                    span::Span::merge(spans)
+                }}",
+                lhs,
+                name,
+                vars,
+                spans.len(),
+                spans.join(", ")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+
+    format!(
+        "impl span::Spanned for {} {{
+            fn span(&self) -> Option<span::Span> {{
+                match self {{
+                    {}
+                }}
+            }}
+        }}",
+        lhs, arms
+    )
+}
+
+// Vec<(省略記法の種類, 要素の記号)>
+fn get_fields(stream: TokenStream) -> Vec<(Option<String>, String)> {
+    let mut ret = vec![];
+    let mut it = stream.into_iter().peekable();
+
+    while let Some(tt) = it.next() {
+        let symbol = tt.to_string();
+
+        if matches!(it.peek(), Some(Punct(p)) if p.as_char() == '<') {
+            it.next();
+            let x = it.next().unwrap().to_string();
+            for tt in it.by_ref() {
+                if matches!(&tt, Punct(p) if p.as_char() == '>') {
+                    break;
+                }
+            }
+            ret.push((Some(symbol), x));
+        } else {
+            ret.push((None, symbol));
+        }
+    }
+
+    ret
+}
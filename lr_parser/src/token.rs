@@ -1,23 +1,26 @@
 use proc_macro::{TokenTree::*, *};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-pub fn get_terminal_symbols(arg: Option<TokenTree>) -> HashSet<String> {
+// 終端記号と，その名前の位置
+pub fn get_terminal_symbols(arg: Option<TokenTree>) -> (HashSet<String>, HashMap<String, Span>) {
     if let Some(Group(grp)) = arg {
-        let mut ret: HashSet<_> = grp
+        let spans: HashMap<_, _> = grp
             .stream()
             .into_iter()
             .map(|tt| {
                 if let Ident(id) = tt {
-                    id.to_string()
+                    (id.to_string(), id.span())
                 } else {
                     panic!()
                 }
             })
             .collect();
 
+        let mut ret: HashSet<_> = spans.keys().cloned().collect();
+
         ret.insert("F_".to_string());
 
-        ret
+        (ret, spans)
     } else {
         panic!()
     }
@@ -0,0 +1,320 @@
+use proc_macro::Span;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::ebnf;
+use crate::error::{GrammarError, GrammarWarning, Location};
+use crate::item::Item;
+use crate::precedence::Precedence;
+use crate::rule::RuleInfo;
+
+// 表を作る前に文法の誤りを探す
+pub fn validate(
+    algorithm: &str,
+    start_symbol: &str,
+    terminal_symbols: &HashSet<String>,
+    token_spans: &HashMap<String, Span>,
+    nonterminal_symbols: &HashSet<String>,
+    map_lhs2items: &HashMap<String, HashSet<Item>>,
+    rule_info: &RuleInfo,
+) -> (Vec<GrammarError>, Vec<GrammarWarning>) {
+    let mut errors = vec![];
+    let mut warnings = vec![];
+
+    let mut items: Vec<_> = map_lhs2items
+        .values()
+        .flatten()
+        .filter(|x| x.get_lhs() != "S_")
+        .collect();
+    items.sort();
+
+    let mut nonterminals: Vec<_> = nonterminal_symbols.iter().filter(|x| *x != "S_").collect();
+    nonterminals.sort();
+
+    // 補助記号の誤りは展開元の記号の誤りとして報告される
+    let user_nonterminals: Vec<_> = nonterminals
+        .iter()
+        .copied()
+        .filter(|x| !ebnf::is_helper(x))
+        .collect();
+
+    // 定義されていない記号
+    if !nonterminal_symbols.contains(start_symbol) {
+        errors.push(GrammarError {
+            location: Location::Start,
+            message: format!(
+                "The start symbol is not defined in RULE! ({})",
+                start_symbol
+            ),
+        });
+    }
+
+    // 補助記号の規則はどれも展開元の欄を指すので，同じ記号は一度だけ報告する
+    let mut reported_in_helpers = HashSet::new();
+    for item in &items {
+        let rhs_spans = &rule_info.rhs_spans[&item.get_rule_number()];
+        for (symbol, &span) in item.get_rhs().iter().zip(rhs_spans) {
+            if ebnf::is_helper(item.get_lhs()) && !reported_in_helpers.insert(symbol) {
+                continue;
+            }
+
+            if !terminal_symbols.contains(symbol) && !nonterminal_symbols.contains(symbol) {
+                errors.push(GrammarError {
+                    location: Location::Span(span),
+                    message: format!(
+                        "The symbol is defined neither in TOKEN nor in RULE! ({})",
+                        symbol
+                    ),
+                });
+            }
+        }
+    }
+
+    // errorで回復できるのはLRだけ
+    if !matches!(algorithm, "LR0" | "SLR" | "LALR" | "LR1" | "PGM") {
+        for item in &items {
+            let rhs_spans = &rule_info.rhs_spans[&item.get_rule_number()];
+            for (symbol, &span) in item.get_rhs().iter().zip(rhs_spans) {
+                if symbol == "error" {
+                    errors.push(GrammarError {
+                        location: Location::Span(span),
+                        message: format!(
+                            "The error token can be used only by LR0, SLR, LALR, LR1 and PGM! ({})",
+                            algorithm
+                        ),
+                    });
+                }
+            }
+        }
+    }
+
+    // 型を宣言した非終端記号の規則だけが意味動作を持つ
+    for item in &items {
+        let typed = rule_info.typed_symbols.contains(item.get_lhs());
+        let has_action = rule_info.actions.contains_key(&item.get_rule_number());
+
+        let message = match (typed, has_action) {
+            (true, false) => "The production of a typed nonterminal needs an action!",
+            (false, true) => "The production with an action needs the type of its nonterminal!",
+            _ => continue,
+        };
+        errors.push(GrammarError {
+            location: Location::Rule(item.get_rule_number()),
+            message: format!("{} ({})", message, item.get_rule_name()),
+        });
+    }
+
+    // 以降は全ての記号が定義されていることを前提にする
+    if !errors.is_empty() {
+        return (errors, warnings);
+    }
+
+    // 規則で使われていない終端記号
+    let used_symbols: HashSet<_> = items.iter().flat_map(|x| x.get_rhs()).collect();
+
+    let mut tokens: Vec<_> = token_spans.iter().collect();
+    tokens.sort_by_key(|(x, _)| *x);
+
+    for (symbol, &span) in tokens {
+        if !used_symbols.contains(symbol) {
+            warnings.push(GrammarWarning {
+                span,
+                message: format!("The token is never used in RULE! ({})", symbol),
+            });
+        }
+    }
+
+    // 開始記号から到達できない非終端記号
+    let reachable_symbols = {
+        let mut tmp = HashSet::from([start_symbol]);
+        let mut vdq = VecDeque::from([start_symbol]);
+
+        while let Some(lhs) = vdq.pop_front() {
+            for item in &map_lhs2items[lhs] {
+                for symbol in item.get_rhs() {
+                    if nonterminal_symbols.contains(symbol) && tmp.insert(symbol) {
+                        vdq.push_back(symbol);
+                    }
+                }
+            }
+        }
+
+        tmp
+    };
+
+    for &symbol in &user_nonterminals {
+        if !reachable_symbols.contains(&symbol[..]) {
+            warnings.push(GrammarWarning {
+                span: rule_info.lhs_spans[symbol],
+                message: format!(
+                    "The nonterminal is unreachable from {}! ({})",
+                    start_symbol, symbol
+                ),
+            });
+        }
+    }
+
+    // 終端記号列を導出できない非終端記号
+    let productive_symbols = compute_symbols_deriving(&items, |x| !nonterminal_symbols.contains(x));
+
+    for &symbol in &user_nonterminals {
+        if !productive_symbols.contains(&symbol[..]) {
+            errors.push(GrammarError {
+                location: Location::Span(rule_info.lhs_spans[symbol]),
+                message: format!("The nonterminal derives no string of tokens! ({})", symbol),
+            });
+        }
+    }
+
+    // 自分自身を導出する非終端記号
+    let nullable_symbols = compute_symbols_deriving(&items, |_| false);
+
+    // Map<非終端記号A, Vec<(A =>+ BとなるB, その規則番号)>>
+    let mut unit_map: HashMap<&str, Vec<(&str, usize)>> = HashMap::new();
+    for item in &items {
+        let rhs = item.get_rhs();
+        for (i, symbol) in rhs.iter().enumerate() {
+            if nonterminal_symbols.contains(symbol)
+                && rhs[..i]
+                    .iter()
+                    .chain(&rhs[i + 1..])
+                    .all(|x| nullable_symbols.contains(&x[..]))
+            {
+                unit_map
+                    .entry(item.get_lhs())
+                    .or_default()
+                    .push((symbol, item.get_rule_number()));
+            }
+        }
+    }
+
+    let mut reported = HashSet::new();
+    for &symbol in &nonterminals {
+        if reported.contains(&symbol[..]) {
+            continue;
+        }
+
+        if let Some((path, rule)) = find_cycle(&unit_map, symbol) {
+            errors.push(GrammarError {
+                location: Location::Rule(rule),
+                message: format!("The nonterminal derives itself! ({})", path.join(" => ")),
+            });
+            reported.extend(path);
+        }
+    }
+
+    (errors, warnings)
+}
+
+// 全ての記号がis_baseであるような列を導出できる非終端記号の集合
+fn compute_symbols_deriving<'a>(
+    items: &[&'a Item],
+    is_base: impl Fn(&str) -> bool,
+) -> HashSet<&'a str> {
+    let mut ret = HashSet::new();
+
+    loop {
+        let mut not_changed = true;
+
+        for item in items {
+            if !ret.contains(item.get_lhs())
+                && item
+                    .get_rhs()
+                    .iter()
+                    .all(|x| is_base(x) || ret.contains(&x[..]))
+            {
+                ret.insert(item.get_lhs());
+                not_changed = false;
+            }
+        }
+
+        if not_changed {
+            break;
+        }
+    }
+
+    ret
+}
+
+// symbolからsymbolに戻る最短の閉路と，その最初の規則番号
+fn find_cycle<'a>(
+    unit_map: &HashMap<&'a str, Vec<(&'a str, usize)>>,
+    symbol: &'a str,
+) -> Option<(Vec<&'a str>, usize)> {
+    // Map<記号, (直前の記号, 規則番号)>
+    let mut parent: HashMap<&str, (&str, usize)> = HashMap::new();
+    let mut vdq = VecDeque::from([symbol]);
+
+    while let Some(u) = vdq.pop_front() {
+        for &(v, rule) in unit_map.get(u).into_iter().flatten() {
+            if parent.contains_key(v) {
+                continue;
+            }
+            parent.insert(v, (u, rule));
+
+            if v == symbol {
+                let mut path = vec![symbol];
+                let mut first_rule = rule;
+                let mut w = u;
+                while w != symbol {
+                    path.push(w);
+                    let (x, rule) = parent[w];
+                    first_rule = rule;
+                    w = x;
+                }
+                path.push(symbol);
+                path.reverse();
+
+                return Some((path, first_rule));
+            }
+
+            vdq.push_back(v);
+        }
+    }
+
+    None
+}
+
+// 優先順位で衝突を解決できるのはLRの表だけ
+pub fn validate_precedence(
+    algorithm: &str,
+    rule_info: &RuleInfo,
+    precedence: &Precedence,
+) -> Vec<GrammarError> {
+    let mut errors = vec![];
+
+    let mut prec_symbols: Vec<_> = rule_info.prec_symbols.iter().collect();
+    prec_symbols.sort();
+
+    if matches!(algorithm, "LR0" | "SLR" | "LALR" | "LR1" | "PGM") {
+        for (n, symbol) in prec_symbols {
+            if !precedence.has_precedence(symbol) {
+                errors.push(GrammarError {
+                    location: Location::Span(rule_info.prec_spans[n]),
+                    message: format!("The symbol given to %prec has no precedence! ({})", symbol),
+                });
+            }
+        }
+        return errors;
+    }
+
+    if let Some(span) = precedence.span() {
+        errors.push(GrammarError {
+            location: Location::Span(span),
+            message: format!(
+                "PRECEDENCE can be used only by LR0, SLR, LALR, LR1 and PGM! ({})",
+                algorithm
+            ),
+        });
+    }
+    for (n, _) in prec_symbols {
+        errors.push(GrammarError {
+            location: Location::Span(rule_info.prec_spans[n]),
+            message: format!(
+                "%prec can be used only by LR0, SLR, LALR, LR1 and PGM! ({})",
+                algorithm
+            ),
+        });
+    }
+
+    errors
+}
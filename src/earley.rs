@@ -0,0 +1,349 @@
+// ALGORITHM { Earley } の実行時．左再帰や空規則，曖昧さを含む任意の文脈自由文法を受理する
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use crate::forest::{Forest, Rule};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Item {
+    rule: usize,
+    dot: usize,
+    origin: usize,
+}
+
+impl Item {
+    fn next(self) -> Self {
+        Self {
+            dot: self.dot + 1,
+            ..self
+        }
+    }
+}
+
+// (記号, 始まり, 終わり)
+type Span = (usize, usize, usize);
+// (左辺, 始まり, 規則, 子)
+type Alternative = (usize, usize, usize, Vec<Span>);
+// (記号, 始まり)から(位置, 記号)の並び
+type Chains = HashMap<(usize, usize), Vec<(usize, usize)>>;
+
+struct Chart<'a> {
+    rules: &'a [Rule],
+    terminals: Vec<usize>,
+    // sets[i] : 位置iのEarley項と，項の直前の記号を読み始めた位置
+    sets: Vec<HashMap<Item, Vec<usize>>>,
+    queues: Vec<Vec<Item>>,
+    // waiting[i][x] : 位置iで記号xを待っている項
+    waiting: Vec<HashMap<usize, Vec<Item>>>,
+    // Leoの最適化．位置iから読んだxを完了すると完了が一本道に続くとき，その一番上で待っている項と位置
+    leo: HashMap<(usize, usize), Option<(Item, usize)>>,
+    // chains[i][(x, j)] : Leoの最適化で飛ばした，xがj..iを導出するまでの完了の始まり
+    chains: Vec<Chains>,
+}
+
+// 入力は終端を表す記号で終わる．symbolは終端記号の番号を返し，rules[0]は S_ -> start F_ とする．
+// 失敗したらどの項も読めなかった字句の位置を返す
+pub fn parse<T>(
+    input: &[T],
+    symbol: &dyn Fn(&T) -> usize,
+    rules: &[Rule],
+) -> Result<(Forest, usize), usize> {
+    let n = input.len();
+
+    let mut rules_by_lhs: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, rule) in rules.iter().enumerate() {
+        rules_by_lhs.entry(rule.lhs).or_default().push(i);
+    }
+
+    let nullable_symbols = compute_nullable_symbols(rules);
+
+    let mut chart = Chart {
+        rules,
+        terminals: input.iter().map(symbol).collect(),
+        sets: vec![HashMap::new(); n + 1],
+        queues: vec![vec![]; n + 1],
+        waiting: vec![HashMap::new(); n + 1],
+        leo: HashMap::new(),
+        chains: vec![HashMap::new(); n + 1],
+    };
+
+    chart.add(
+        0,
+        Item {
+            rule: 0,
+            dot: 0,
+            origin: 0,
+        },
+        None,
+    );
+
+    for i in 0..=n {
+        let mut k = 0;
+
+        while k < chart.queues[i].len() {
+            let item = chart.queues[i][k];
+            k += 1;
+
+            if let Some(&x) = rules[item.rule].rhs.get(item.dot) {
+                if let Some(v) = rules_by_lhs.get(&x) {
+                    // predict
+                    for &rule in v {
+                        chart.add(
+                            i,
+                            Item {
+                                rule,
+                                dot: 0,
+                                origin: i,
+                            },
+                            None,
+                        );
+                    }
+
+                    // 空列を導出できる記号は読み飛ばしてよい
+                    if nullable_symbols.contains(&x) {
+                        chart.add(i, item.next(), Some(i));
+                    }
+                } else if i < n && chart.terminals[i] == x {
+                    // scan
+                    chart.add(i + 1, item.next(), Some(i));
+                }
+            } else {
+                chart.complete(i, item);
+            }
+        }
+
+        if i < n && chart.queues[i + 1].is_empty() {
+            return Err(i);
+        }
+    }
+
+    let accept_item = Item {
+        rule: 0,
+        dot: rules[0].rhs.len(),
+        origin: 0,
+    };
+    if !chart.sets[n].contains_key(&accept_item) {
+        return Err(n);
+    }
+
+    let mut forest = Forest::new();
+    let root = chart.build(&mut forest, &rules_by_lhs, rules[0].rhs[0], 0, n - 1);
+
+    Ok((forest, root))
+}
+
+fn compute_nullable_symbols(rules: &[Rule]) -> HashSet<usize> {
+    let mut ret = HashSet::new();
+
+    loop {
+        let mut not_changed = true;
+
+        for rule in rules {
+            if !ret.contains(&rule.lhs) && rule.rhs.iter().all(|x| ret.contains(x)) {
+                ret.insert(rule.lhs);
+                not_changed = false;
+            }
+        }
+
+        if not_changed {
+            break;
+        }
+    }
+
+    ret
+}
+
+impl Chart<'_> {
+    // 位置iに項を加える．midは項の直前の記号を読み始めた位置
+    fn add(&mut self, i: usize, item: Item, mid: Option<usize>) {
+        let mids = match self.sets[i].entry(item) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                self.queues[i].push(item);
+                if let Some(&x) = self.rules[item.rule].rhs.get(item.dot) {
+                    self.waiting[i].entry(x).or_default().push(item);
+                }
+                e.insert(vec![])
+            }
+        };
+
+        if let Some(mid) = mid {
+            if !mids.contains(&mid) {
+                mids.push(mid);
+            }
+        }
+    }
+
+    // 位置iで完了した項itemの左辺を待っていた項を進める
+    fn complete(&mut self, i: usize, item: Item) {
+        let lhs = self.rules[item.rule].lhs;
+        let j = item.origin;
+
+        // 位置jの項はもう増えないので，完了の一本道を一度に登れる
+        if j < i {
+            if let Some((top, k)) = self.leo_item(j, lhs) {
+                let x = self.rules[top.rule].rhs[top.dot];
+                if (x, k) != (lhs, j) {
+                    let starts = self.chains[i].entry((x, k)).or_default();
+                    if !starts.contains(&(j, lhs)) {
+                        starts.push((j, lhs));
+                    }
+                }
+
+                self.add(i, top.next(), Some(k));
+                return;
+            }
+        }
+
+        // 空列の完了なら，この後で待ち始める項は読み飛ばしで進む
+        let mut t = 0;
+        while let Some(&parent) = self.waiting[j].get(&lhs).and_then(|v| v.get(t)) {
+            t += 1;
+            self.add(i, parent.next(), Some(j));
+        }
+    }
+
+    // 位置jから読んだxを完了したとき，待っている項が一つだけで，それも次で完了する限り登った一番上の項と位置
+    fn leo_item(&mut self, j: usize, x: usize) -> Option<(Item, usize)> {
+        let mut levels = vec![];
+        let mut key = (j, x);
+
+        let mut top = loop {
+            if let Some(&memo) = self.leo.get(&key) {
+                break memo;
+            }
+
+            // 循環する導出で止まらなくならないように先に印を付ける
+            self.leo.insert(key, None);
+
+            match self.waiting[key.0].get(&key.1).map(Vec::as_slice) {
+                Some(&[parent]) if parent.dot + 1 == self.rules[parent.rule].rhs.len() => {
+                    levels.push((key, parent));
+                    key = (parent.origin, self.rules[parent.rule].lhs);
+                }
+                _ => break None,
+            }
+        };
+
+        for (key, parent) in levels.into_iter().rev() {
+            top = top.or(Some((parent, key.0)));
+            self.leo.insert(key, top);
+        }
+
+        top
+    }
+
+    // symbolがstart..endを導出する節点から辿れる全ての導出を森に加える
+    fn build(
+        &self,
+        forest: &mut Forest,
+        rules_by_lhs: &HashMap<usize, Vec<usize>>,
+        symbol: usize,
+        start: usize,
+        end: usize,
+    ) -> usize {
+        let root = forest.symbol(symbol, start, end);
+        let mut visited = HashSet::from([(symbol, start, end)]);
+        let mut walked = HashSet::new();
+        let mut stack = vec![(symbol, start, end)];
+
+        while let Some((x, s, e)) = stack.pop() {
+            let mut alternatives = vec![];
+
+            for &rule in &rules_by_lhs[&x] {
+                let item = Item {
+                    rule,
+                    dot: self.rules[rule].rhs.len(),
+                    origin: s,
+                };
+
+                if self.sets[e].contains_key(&item) {
+                    for children in self.prefixes(item, e) {
+                        alternatives.push((x, s, rule, children));
+                    }
+                }
+            }
+
+            // Leoの最適化で飛ばした完了を辿り直す
+            for &(j, y) in self.chains[e].get(&(x, s)).into_iter().flatten() {
+                self.walk_chain(j, y, (x, s, e), &mut walked, &mut alternatives);
+            }
+
+            for (x, s, rule, children) in alternatives {
+                let node = forest.symbol(x, s, e);
+                let children = children
+                    .into_iter()
+                    .map(|(y, start, end)| {
+                        if !rules_by_lhs.contains_key(&y) {
+                            forest.leaf(start)
+                        } else {
+                            if visited.insert((y, start, end)) {
+                                stack.push((y, start, end));
+                            }
+                            forest.symbol(y, start, end)
+                        }
+                    })
+                    .collect();
+
+                forest.add_alternative(node, rule, children);
+            }
+        }
+
+        root
+    }
+
+    // 項itemが位置endまでに読んだ記号の，(記号, 始まり, 終わり)の並べ方を全て列挙する
+    fn prefixes(&self, item: Item, end: usize) -> Vec<Vec<Span>> {
+        if item.dot == 0 {
+            return vec![vec![]];
+        }
+
+        let x = self.rules[item.rule].rhs[item.dot - 1];
+        let previous = Item {
+            dot: item.dot - 1,
+            ..item
+        };
+
+        let mut ret = vec![];
+
+        for &mid in &self.sets[end][&item] {
+            for mut children in self.prefixes(previous, mid) {
+                children.push((x, mid, end));
+                ret.push(children);
+            }
+        }
+
+        ret
+    }
+
+    // 位置jから読んだyの完了から，topの節点まで飛ばした完了の選択肢を作る．
+    // 既に辿った節点より上は他の連鎖と同じなので辿らない
+    fn walk_chain(
+        &self,
+        mut j: usize,
+        mut y: usize,
+        top: Span,
+        walked: &mut HashSet<Span>,
+        alternatives: &mut Vec<Alternative>,
+    ) {
+        let (x, s, e) = top;
+
+        loop {
+            let parent = self.waiting[j][&y][0];
+            let lhs = self.rules[parent.rule].lhs;
+
+            for mut children in self.prefixes(parent, j) {
+                children.push((y, j, e));
+                alternatives.push((lhs, parent.origin, parent.rule, children));
+            }
+
+            if (lhs, parent.origin) == (x, s) || !walked.insert((lhs, parent.origin, e)) {
+                break;
+            }
+
+            j = parent.origin;
+            y = lhs;
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt;
+
+use crate::span::{Span, Spanned};
+
+// Parser::parseの誤り．行と列は1から数え，列は文字単位で数える
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    // spanの入力に一致する字句がないか，字句の文字列を値に変換できなかった
+    LexError {
+        span: Span,
+        line: usize,
+        col: usize,
+        message: String,
+    },
+
+    // textから読んだ字句tokenを受理できなかった．expectedは受理できた字句の表示名，
+    // stateはLRのオートマトンがあればその状態
+    UnexpectedToken {
+        span: Span,
+        line: usize,
+        col: usize,
+        token: &'static str,
+        text: String,
+        expected: Vec<&'static str>,
+        state: Option<usize>,
+    },
+
+    // 続きを読むはずのところで入力が終わった
+    UnexpectedEof {
+        span: Span,
+        line: usize,
+        col: usize,
+        expected: Vec<&'static str>,
+        state: Option<usize>,
+    },
+
+    // 入力に木が二つ以上ある
+    Ambiguous,
+
+    // 文法が不正．実行時に報告するのはALGORITHM { ... Lazy }のときだけ
+    Grammar(String),
+}
+
+impl ParseError {
+    // 誤りが指す入力の範囲
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ParseError::LexError { span, .. }
+            | ParseError::UnexpectedToken { span, .. }
+            | ParseError::UnexpectedEof { span, .. } => Some(*span),
+            ParseError::Ambiguous | ParseError::Grammar(_) => None,
+        }
+    }
+
+    // 位置を除いた誤り
+    fn without_position(&self) -> ParseError {
+        let mut ret = self.clone();
+        match &mut ret {
+            ParseError::LexError {
+                span, line, col, ..
+            }
+            | ParseError::UnexpectedToken {
+                span, line, col, ..
+            }
+            | ParseError::UnexpectedEof {
+                span, line, col, ..
+            } => {
+                *span = Span::default();
+                *line = 0;
+                *col = 0;
+            }
+            ParseError::Ambiguous | ParseError::Grammar(_) => {}
+        }
+        ret
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::LexError {
+                line, col, message, ..
+            } => write!(
+                f,
+                "TokenizeError at Line {}, Col {}: {}",
+                line, col, message
+            ),
+
+            ParseError::UnexpectedToken {
+                line,
+                col,
+                text,
+                expected,
+                ..
+            } => write!(
+                f,
+                "ParseError at Line {}, Col {}: unexpected \"{}\"{}",
+                line,
+                col,
+                text,
+                ExpectedList(expected)
+            ),
+
+            ParseError::UnexpectedEof {
+                line,
+                col,
+                expected,
+                ..
+            } => write!(
+                f,
+                "ParseError at Line {}, Col {}: unexpected end of input{}",
+                line,
+                col,
+                ExpectedList(expected)
+            ),
+
+            ParseError::Ambiguous => write!(f, "AmbiguityError!"),
+
+            ParseError::Grammar(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+struct ExpectedList<'a>(&'a [&'static str]);
+
+impl fmt::Display for ExpectedList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            [] => Ok(()),
+            [x] => write!(f, ", expected {}", x),
+            _ => write!(f, ", expected one of: {}", self.0.join(", ")),
+        }
+    }
+}
+
+// 規則に書いたerror疑似字句の値で，回復した構文の誤り．
+// 何も表示せず，字句と同じく位置を除いて比べる
+#[derive(Debug, Clone)]
+pub struct Recovered {
+    pub error: ParseError,
+}
+
+impl PartialEq for Recovered {
+    fn eq(&self, other: &Self) -> bool {
+        self.error.without_position() == other.error.without_position()
+    }
+}
+
+impl Eq for Recovered {}
+
+impl fmt::Display for Recovered {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+impl Spanned for Recovered {
+    fn span(&self) -> Option<Span> {
+        self.error.span()
+    }
+}
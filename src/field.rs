@@ -0,0 +1,33 @@
+use std::fmt::Display;
+
+// 規則の欄の表示．Option<X>やVec<X>の欄は要素を空白区切りで並べ，空なら何も表示しない
+pub trait DisplayField {
+    fn display_field(&self) -> String;
+}
+
+impl<T: Display> DisplayField for Box<T> {
+    fn display_field(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl<T: DisplayField> DisplayField for Option<T> {
+    fn display_field(&self) -> String {
+        self.as_ref().map_or(String::new(), |x| x.display_field())
+    }
+}
+
+impl<T: Display> DisplayField for Vec<T> {
+    fn display_field(&self) -> String {
+        join(self.iter().map(|x| x.to_string()))
+    }
+}
+
+// 空の欄を飛ばして空白でつなぐ
+pub fn join(fields: impl IntoIterator<Item = String>) -> String {
+    fields
+        .into_iter()
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
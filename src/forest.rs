@@ -0,0 +1,441 @@
+// GLRとEarleyが作る共有された森．同じ部分の導出は一つの節点にまとめる
+
+use std::collections::{HashMap, HashSet};
+
+// 生成したパーサが記号に番号を付けた規則 lhs -> rhs
+pub struct Rule {
+    pub lhs: usize,
+    pub rhs: &'static [usize],
+}
+
+enum Node {
+    // 入力の位置
+    Leaf(usize),
+    // (規則の番号, 子の節点)の選択肢
+    Symbol(Vec<(usize, Vec<usize>)>),
+}
+
+// 入力の全ての導出．記号の節点は，その記号が入力のある範囲を導出する全ての方法をまとめる
+#[derive(Default)]
+pub struct Forest {
+    nodes: Vec<Node>,
+    leaf_map: HashMap<usize, usize>,
+    symbol_map: HashMap<(usize, usize, usize), usize>,
+}
+
+impl Forest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 位置posの終端記号の節点
+    pub fn leaf(&mut self, pos: usize) -> usize {
+        if let Some(&node) = self.leaf_map.get(&pos) {
+            return node;
+        }
+
+        self.nodes.push(Node::Leaf(pos));
+        self.leaf_map.insert(pos, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    // symbolがstart..endの字句を導出する節点
+    pub fn symbol(&mut self, symbol: usize, start: usize, end: usize) -> usize {
+        if let Some(node) = self.get(symbol, start, end) {
+            return node;
+        }
+
+        self.nodes.push(Node::Symbol(vec![]));
+        self.symbol_map
+            .insert((symbol, start, end), self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    pub fn get(&self, symbol: usize, start: usize, end: usize) -> Option<usize> {
+        self.symbol_map.get(&(symbol, start, end)).copied()
+    }
+
+    // childrenに規則ruleを適用したものをnodeの選択肢に加える．既にあればfalse
+    pub fn add_alternative(&mut self, node: usize, rule: usize, children: Vec<usize>) -> bool {
+        if let Node::Symbol(alternatives) = &mut self.nodes[node] {
+            let alternative = (rule, children);
+            if !alternatives.contains(&alternative) {
+                alternatives.push(alternative);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // rootから辿れる節点に，選択肢が二つ以上あるものがあるか
+    pub fn is_ambiguous(&self, root: usize) -> bool {
+        let mut visited = HashSet::from([root]);
+        let mut stack = vec![root];
+
+        while let Some(node) = stack.pop() {
+            if let Node::Symbol(alternatives) = &self.nodes[node] {
+                if alternatives.len() > 1 {
+                    return true;
+                }
+
+                for (_, children) in alternatives {
+                    for &child in children {
+                        if visited.insert(child) {
+                            stack.push(child);
+                        }
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    // rootから導出される全ての木を，求められるたびに一つずつ作る．
+    // leafは入力の位置を終端記号に，reduceは規則を子に適用する．A => A のような循環する導出は除く
+    pub fn trees<'a, T>(
+        &'a self,
+        root: usize,
+        leaf: impl Fn(usize) -> T + 'a,
+        reduce: impl Fn(usize, Vec<T>) -> T + 'a,
+    ) -> Trees<'a, T> {
+        Trees {
+            forest: self,
+            root,
+            leaf: Box::new(leaf),
+            reduce: Box::new(reduce),
+            choices: vec![],
+            started: false,
+        }
+    }
+
+    // rootから導出される木を一つ作る．曖昧な箇所ではchooseが候補の木から残すものの番号を返す．
+    // 範囲外の番号を返したらNone
+    pub fn tree<T: Clone>(
+        &self,
+        root: usize,
+        leaf: &dyn Fn(usize) -> T,
+        reduce: &dyn Fn(usize, Vec<T>) -> T,
+        choose: &dyn Fn(&[T]) -> usize,
+    ) -> Option<T> {
+        if let Node::Leaf(pos) = self.nodes[root] {
+            return Some(leaf(pos));
+        }
+
+        // 循環に含まれない節点の木は，どこから辿っても同じなので一度だけ作る．
+        // 最後に使うときは複製せずに取り出す
+        let cyclic = self.cyclic_nodes(root);
+        let mut uses = self.count_uses(root);
+        let mut memo: HashMap<usize, Option<T>> = HashMap::new();
+
+        // 再帰の代わりに，根から辿っている節点を積む
+        let mut stack = vec![Frame::new(root)];
+        let mut visiting = HashSet::from([root]);
+        let mut returned = None;
+
+        loop {
+            let frame = stack.last_mut().unwrap();
+            let Node::Symbol(alternatives) = &self.nodes[frame.node] else {
+                unreachable!()
+            };
+
+            if let Some(tree) = returned.take() {
+                frame.receive(tree);
+            }
+
+            let mut next = None;
+            while let Some((rule, children)) = alternatives.get(frame.alternative) {
+                let Some(&child) = children.get(frame.children.len()).filter(|_| !frame.dead)
+                else {
+                    frame.finish_alternative(*rule, reduce);
+                    continue;
+                };
+
+                match &self.nodes[child] {
+                    Node::Leaf(pos) => frame.children.push(leaf(*pos)),
+                    Node::Symbol(_) if memo.contains_key(&child) => {
+                        let count = uses.get_mut(&child).unwrap();
+                        *count -= 1;
+                        let tree = if *count == 0 {
+                            memo.remove(&child).unwrap()
+                        } else {
+                            memo[&child].clone()
+                        };
+                        frame.receive(tree);
+                    }
+                    // 循環する導出は打ち切る
+                    Node::Symbol(_) if visiting.contains(&child) => frame.dead = true,
+                    Node::Symbol(_) => {
+                        next = Some(child);
+                        break;
+                    }
+                }
+            }
+
+            if let Some(child) = next {
+                visiting.insert(child);
+                stack.push(Frame::new(child));
+                continue;
+            }
+
+            let frame = stack.pop().unwrap();
+            visiting.remove(&frame.node);
+
+            let mut candidates = frame.candidates;
+            let tree = if candidates.len() > 1 {
+                // 範囲外の番号が選ばれたら木を作らない
+                let i = choose(&candidates);
+                (i < candidates.len()).then(|| candidates.swap_remove(i))
+            } else {
+                candidates.pop()
+            };
+
+            if stack.is_empty() {
+                return tree;
+            }
+
+            if !cyclic.contains(&frame.node) {
+                let count = uses.get_mut(&frame.node).unwrap();
+                *count -= 1;
+                if *count > 0 {
+                    memo.insert(frame.node, tree.clone());
+                }
+            }
+
+            returned = Some(tree);
+        }
+    }
+
+    // 節点の全ての選択肢の子
+    fn children(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        let alternatives = match &self.nodes[node] {
+            Node::Leaf(_) => &[][..],
+            Node::Symbol(alternatives) => &alternatives[..],
+        };
+        alternatives
+            .iter()
+            .flat_map(|(_, children)| children.iter().copied())
+            .filter(|&x| matches!(self.nodes[x], Node::Symbol(_)))
+    }
+
+    // rootから辿れる節点が，選択肢の子として現れる回数
+    fn count_uses(&self, root: usize) -> HashMap<usize, usize> {
+        let mut ret = HashMap::new();
+        let mut visited = HashSet::from([root]);
+        let mut stack = vec![root];
+
+        while let Some(node) = stack.pop() {
+            for child in self.children(node) {
+                *ret.entry(child).or_insert(0) += 1;
+                if visited.insert(child) {
+                    stack.push(child);
+                }
+            }
+        }
+
+        ret
+    }
+
+    // rootから辿れる節点のうち，循環する導出に含まれるもの．Tarjanの強連結成分分解で求める
+    fn cyclic_nodes(&self, root: usize) -> HashSet<usize> {
+        let mut ret = HashSet::new();
+        // 節点 -> (訪れた順番, 辿れる最も早い順番)
+        let mut order = HashMap::from([(root, (0, 0))]);
+        // 成分がまだ決まっていない節点
+        let mut component = vec![root];
+        let mut on_component = HashSet::from([root]);
+        // 再帰の代わりに，節点と残りの子を積む
+        let mut stack: Vec<(usize, Vec<usize>)> = vec![(root, self.children(root).collect())];
+
+        while let Some(&mut (node, ref mut children)) = stack.last_mut() {
+            if let Some(child) = children.pop() {
+                if child == node {
+                    ret.insert(node);
+                }
+
+                match order.get(&child) {
+                    None => {
+                        let n = order.len();
+                        order.insert(child, (n, n));
+                        component.push(child);
+                        on_component.insert(child);
+                        stack.push((child, self.children(child).collect()));
+                    }
+                    Some(&(n, _)) if on_component.contains(&child) => {
+                        let low = &mut order.get_mut(&node).unwrap().1;
+                        *low = (*low).min(n);
+                    }
+                    Some(_) => {}
+                }
+                continue;
+            }
+
+            stack.pop();
+            let (n, low) = order[&node];
+            if let Some((parent, _)) = stack.last() {
+                let parent_low = &mut order.get_mut(parent).unwrap().1;
+                *parent_low = (*parent_low).min(low);
+            }
+
+            if n == low {
+                let i = component.iter().rposition(|&x| x == node).unwrap();
+                let nodes = component.split_off(i);
+                for x in &nodes {
+                    on_component.remove(x);
+                }
+                if nodes.len() > 1 {
+                    ret.extend(nodes);
+                }
+            }
+        }
+
+        ret
+    }
+}
+
+// 木を作っている途中の節点
+struct Frame<T> {
+    node: usize,
+    // 試している選択肢の番号
+    alternative: usize,
+    // その選択肢の，作り終えた子の木
+    children: Vec<T>,
+    // その選択肢に木のない子があった
+    dead: bool,
+    // 作り終えた選択肢の木
+    candidates: Vec<T>,
+}
+
+impl<T> Frame<T> {
+    fn new(node: usize) -> Self {
+        Self {
+            node,
+            alternative: 0,
+            children: vec![],
+            dead: false,
+            candidates: vec![],
+        }
+    }
+
+    fn receive(&mut self, tree: Option<T>) {
+        match tree {
+            Some(tree) => self.children.push(tree),
+            None => self.dead = true,
+        }
+    }
+
+    fn finish_alternative(&mut self, rule: usize, reduce: &dyn Fn(usize, Vec<T>) -> T) {
+        let children = std::mem::take(&mut self.children);
+        if !self.dead {
+            self.candidates.push(reduce(rule, children));
+        }
+        self.dead = false;
+        self.alternative += 1;
+    }
+}
+
+// Forest::treesが返す，森の木を一つずつ作るもの．
+// 木は先行順に現れる節点ごとに選んだ選択肢の番号の列で表し，その辞書順に作る
+pub struct Trees<'a, T> {
+    forest: &'a Forest,
+    root: usize,
+    leaf: Box<dyn Fn(usize) -> T + 'a>,
+    reduce: Box<dyn Fn(usize, Vec<T>) -> T + 'a>,
+    // Vec<(節点, 選んだ選択肢の番号)>
+    choices: Vec<(usize, usize)>,
+    started: bool,
+}
+
+impl<T> Trees<'_, T> {
+    // 最後の選択を次の選択肢に進める．どれも進められなければfalse
+    fn advance(&mut self) -> bool {
+        while let Some((node, i)) = self.choices.last_mut() {
+            if let Node::Symbol(alternatives) = &self.forest.nodes[*node] {
+                if *i + 1 < alternatives.len() {
+                    *i += 1;
+                    return true;
+                }
+            }
+            self.choices.pop();
+        }
+
+        false
+    }
+
+    // 選択の列に従って木を作る．列が尽きたら選べる最初の選択肢を選ぶ．
+    // d番目の節点に選べる選択肢がなければErr(d)
+    fn walk(&mut self) -> Result<T, usize> {
+        // 再帰の代わりに，節点に入る印と出る印を積む
+        enum Step {
+            Enter(usize),
+            Exit(usize, usize, usize),
+        }
+
+        let nodes = &self.forest.nodes;
+        let mut steps = vec![Step::Enter(self.root)];
+        let mut values = vec![];
+        let mut visiting = HashSet::new();
+        let mut d = 0;
+
+        while let Some(step) = steps.pop() {
+            match step {
+                Step::Enter(node) => match &nodes[node] {
+                    Node::Leaf(pos) => values.push((self.leaf)(*pos)),
+                    Node::Symbol(alternatives) => {
+                        visiting.insert(node);
+
+                        // 循環する導出は打ち切る
+                        let start = self.choices.get(d).map_or(0, |x| x.1);
+                        let Some(i) = (start..alternatives.len())
+                            .find(|&i| alternatives[i].1.iter().all(|x| !visiting.contains(x)))
+                        else {
+                            return Err(d);
+                        };
+
+                        if d < self.choices.len() {
+                            self.choices[d].1 = i;
+                        } else {
+                            self.choices.push((node, i));
+                        }
+                        d += 1;
+
+                        let (rule, children) = &alternatives[i];
+                        steps.push(Step::Exit(node, *rule, children.len()));
+                        steps.extend(children.iter().rev().map(|&x| Step::Enter(x)));
+                    }
+                },
+                Step::Exit(node, rule, n) => {
+                    visiting.remove(&node);
+                    let children = values.split_off(values.len() - n);
+                    values.push((self.reduce)(rule, children));
+                }
+            }
+        }
+
+        Ok(values.pop().unwrap())
+    }
+}
+
+impl<T> Iterator for Trees<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.started && !self.advance() {
+            return None;
+        }
+        self.started = true;
+
+        loop {
+            match self.walk() {
+                Ok(tree) => return Some(tree),
+                Err(d) => {
+                    self.choices.truncate(d);
+                    if !self.advance() {
+                        return None;
+                    }
+                }
+            }
+        }
+    }
+}
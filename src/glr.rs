@@ -0,0 +1,234 @@
+// ALGORITHM { GLR } の実行時．衝突を含む表で，グラフ構造スタックを使って全ての解析を並行して進める
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+
+use crate::forest::{Forest, Rule};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Shift(usize),
+    Reduce(usize),
+    Accept,
+}
+
+struct Node {
+    state: usize,
+    level: usize,
+    // (一つ下の節点, その間の記号を表す森の節点)
+    edges: Vec<(usize, usize)>,
+    // 同じ位置の節点への辺，つまり空列を導出した記号の辺の番号
+    empty_edges: Vec<usize>,
+}
+
+impl Node {
+    fn new(state: usize, level: usize) -> Self {
+        Self {
+            state,
+            level,
+            edges: vec![],
+            empty_edges: vec![],
+        }
+    }
+}
+
+// 待っている還元 (スタックの先頭, 規則, 通らなければならない辺)
+type Reduction = (usize, usize, Option<(usize, usize)>);
+
+// 末尾が入力の終わりを表す終端記号であるinputを読み，森とその根を返す．
+// actionsは状態と先読み記号(入力の後ではNone)での全ての動作，gotoは非終端記号での遷移先で，
+// rules[0]は拡大した規則 S_ -> start F_ でなければならない．
+// 失敗したら，全てのスタックが読めなかった字句の位置を返す
+pub fn parse<T>(
+    input: &[T],
+    actions: &dyn Fn(usize, Option<&T>) -> Vec<Action>,
+    goto: &dyn Fn(usize, usize) -> Option<usize>,
+    rules: &[Rule],
+) -> Result<(Forest, usize), usize> {
+    let mut forest = Forest::new();
+    let mut nodes = vec![Node::new(0, 0)];
+    // 全ての辺 (上の節点, 下の節点)
+    let mut edge_set = HashSet::new();
+
+    // Map<状態, 同じ位置のスタックの先頭>
+    let mut frontier = BTreeMap::from([(0, 0)]);
+
+    for (i, token) in input.iter().enumerate() {
+        // reduce
+        // 節点が増えたらその全ての還元を，既にある節点に辺が増えたらその辺を通る還元だけを待たせる
+        let mut pending = VecDeque::new();
+        for &v in frontier.values() {
+            push_reductions(&mut pending, v, nodes[v].state, None, token, actions, rules);
+        }
+
+        while let Some((v, rule, edge)) = pending.pop_front() {
+            let lhs = rules[rule].lhs;
+
+            for (w, children) in paths(&nodes, v, rules[rule].rhs.len(), edge, i) {
+                let f = forest.symbol(lhs, nodes[w].level, i);
+                forest.add_alternative(f, rule, children);
+
+                let state = goto(nodes[w].state, lhs).unwrap();
+                let Some(&u) = frontier.get(&state) else {
+                    nodes.push(Node::new(state, i));
+                    let u = nodes.len() - 1;
+                    frontier.insert(state, u);
+                    add_edge(&mut nodes, &mut edge_set, u, w, f);
+                    push_reductions(&mut pending, u, state, None, token, actions, rules);
+                    continue;
+                };
+
+                let Some(k) = add_edge(&mut nodes, &mut edge_set, u, w, f) else {
+                    continue;
+                };
+
+                // 新しい辺を通る経路は，uか，空列を導出した辺でuに至る節点から始まる
+                for &x in frontier.values() {
+                    if x == u || !nodes[x].empty_edges.is_empty() {
+                        push_reductions(
+                            &mut pending,
+                            x,
+                            nodes[x].state,
+                            Some((u, k)),
+                            token,
+                            actions,
+                            rules,
+                        );
+                    }
+                }
+            }
+        }
+
+        // shift
+        let mut next_frontier = BTreeMap::new();
+
+        for &v in frontier.values() {
+            for action in actions(nodes[v].state, Some(token)) {
+                if let Action::Shift(state) = action {
+                    let f = forest.leaf(i);
+                    let u = *next_frontier.entry(state).or_insert_with(|| {
+                        nodes.push(Node::new(state, i + 1));
+                        nodes.len() - 1
+                    });
+                    add_edge(&mut nodes, &mut edge_set, u, v, f);
+                }
+            }
+        }
+
+        if next_frontier.is_empty() {
+            return Err(i);
+        }
+
+        frontier = next_frontier;
+    }
+
+    // accept
+    if frontier
+        .values()
+        .any(|&v| actions(nodes[v].state, None).contains(&Action::Accept))
+    {
+        if let Some(root) = forest.get(rules[0].rhs[0], 0, input.len() - 1) {
+            return Ok((forest, root));
+        }
+    }
+
+    Err(input.len())
+}
+
+// uからwへ森の節点fを表す辺を加え，その番号を返す．既にあればNone
+fn add_edge(
+    nodes: &mut [Node],
+    edge_set: &mut HashSet<(usize, usize)>,
+    u: usize,
+    w: usize,
+    f: usize,
+) -> Option<usize> {
+    if !edge_set.insert((u, w)) {
+        return None;
+    }
+
+    let k = nodes[u].edges.len();
+    nodes[u].edges.push((w, f));
+    if nodes[w].level == nodes[u].level {
+        nodes[u].empty_edges.push(k);
+    }
+
+    Some(k)
+}
+
+// 節点vの状態でのtokenに対する還元を待たせる．edgeがあれば，その辺を通る経路だけを還元する
+fn push_reductions<T>(
+    pending: &mut VecDeque<Reduction>,
+    v: usize,
+    state: usize,
+    edge: Option<(usize, usize)>,
+    token: &T,
+    actions: &dyn Fn(usize, Option<&T>) -> Vec<Action>,
+    rules: &[Rule],
+) {
+    for action in actions(state, Some(token)) {
+        if let Action::Reduce(rule) = action {
+            // 空の規則の経路は辺を通らない
+            if edge.is_none() || !rules[rule].rhs.is_empty() {
+                pending.push_back((v, rule, edge));
+            }
+        }
+    }
+}
+
+// vから長さlenの経路をたどった先の節点と，経路上の森の節点の列．
+// edge = (節点, 辺の番号)があれば，その辺を通る経路だけ
+fn paths(
+    nodes: &[Node],
+    v: usize,
+    len: usize,
+    edge: Option<(usize, usize)>,
+    level: usize,
+) -> Vec<(usize, Vec<usize>)> {
+    let mut ret = vec![];
+    collect_paths(nodes, v, len, edge, level, &mut vec![], &mut ret);
+    ret
+}
+
+fn collect_paths(
+    nodes: &[Node],
+    v: usize,
+    len: usize,
+    edge: Option<(usize, usize)>,
+    level: usize,
+    path: &mut Vec<usize>,
+    ret: &mut Vec<(usize, Vec<usize>)>,
+) {
+    if len == 0 {
+        if edge.is_none() {
+            ret.push((v, path.iter().rev().copied().collect()));
+        }
+        return;
+    }
+
+    let mut visit = |k: usize, rest: Option<(usize, usize)>, path: &mut Vec<usize>| {
+        let (w, f) = nodes[v].edges[k];
+        path.push(f);
+        collect_paths(nodes, w, len - 1, rest, level, path, ret);
+        path.pop();
+    };
+
+    match edge {
+        None => {
+            for k in 0..nodes[v].edges.len() {
+                visit(k, None, path);
+            }
+        }
+        // 指定の辺は位置levelの節点から出るので，そこまでは空列を導出した辺だけを通る
+        Some((u, k)) if nodes[v].level == level => {
+            if u == v {
+                visit(k, None, path);
+            }
+            for &x in &nodes[v].empty_edges {
+                if (v, x) != (u, k) {
+                    visit(x, edge, path);
+                }
+            }
+        }
+        Some(_) => {}
+    }
+}
@@ -0,0 +1,376 @@
+// 字句の正規表現を一つのオートマトンにまとめ，位置を固定して照合する．
+// 正規表現を一つずつ先読みして探すと，入力の長さの二乗の時間がかかる
+
+use regex::Regex as WhitespaceRegex;
+use regex_automata::{
+    meta::{self, Regex},
+    Anchored, Input, MatchKind, PatternSet,
+};
+
+use crate::span::Span;
+
+// 字句の文字列から字句を作る．値に変換できなければその理由を返す
+pub type Constructor<T> = fn(&str) -> Result<T, String>;
+
+// 字句解析を始めるモード．モードを指定しない字句はこれに属する
+pub const INITIAL: &str = "INITIAL";
+
+// 字句を読んだときのモードのスタックの操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Push(&'static str),
+    Pop,
+    Switch(&'static str),
+}
+
+// TOKENで宣言した字句
+pub struct Pattern<T> {
+    pub modes: &'static [&'static str],
+    pub regex: String,
+    pub priority: i32,
+    pub constructor: Constructor<T>,
+    pub action: Option<Action>,
+}
+
+// SKIPで宣言した読み飛ばすもの．入れ子のコメントなら，regexは始まりに，closeは終わりに一致する
+pub struct SkipPattern {
+    pub kind: &'static str,
+    pub modes: &'static [&'static str],
+    pub regex: String,
+    pub close: Option<String>,
+}
+
+// コメントなど，SKIPで読み飛ばした入力．kindはSKIPでの名前
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trivia {
+    pub kind: &'static str,
+    pub text: String,
+    pub span: Span,
+}
+
+// 読み飛ばす正規表現．入れ子のコメントは始まりと終わりを一度に探す
+struct Skip {
+    kind: &'static str,
+    regex: Regex,
+    // 始まりと終わりのどちらが先に現れるか
+    delimiters: Option<Regex>,
+}
+
+impl Skip {
+    fn new(pattern: &SkipPattern) -> Self {
+        let regex = Regex::new(&pattern.regex).unwrap();
+
+        let delimiters = pattern.close.as_ref().map(|close| {
+            let regex = Regex::new_many(&[&pattern.regex, close]).unwrap();
+            if regex.is_match("") {
+                panic!(
+                    "The start and the end of a nested comment must not match the empty string! ({})",
+                    pattern.kind
+                );
+            }
+            regex
+        });
+
+        Self {
+            kind: pattern.kind,
+            regex,
+            delimiters,
+        }
+    }
+
+    // posから読み飛ばせる範囲の終わり．入れ子のコメントが閉じていなければ，その始まりと理由
+    fn find_at(&self, s: &str, pos: usize) -> Option<Result<usize, (Span, String)>> {
+        let input = Input::new(s).range(pos..).anchored(Anchored::Yes);
+        let mut end = self.regex.search(&input)?.end();
+        if end == pos {
+            return None;
+        }
+
+        let Some(delimiters) = &self.delimiters else {
+            return Some(Ok(end));
+        };
+
+        let mut depth = 1;
+        while depth > 0 {
+            match delimiters.search(&Input::new(s).range(end..)) {
+                Some(mat) => {
+                    depth = if mat.pattern().as_usize() == 0 {
+                        depth + 1
+                    } else {
+                        depth - 1
+                    };
+                    end = mat.end();
+                }
+                None => {
+                    let start = self.regex.search(&input).unwrap().end();
+                    return Some(Err((
+                        Span::new(pos, start),
+                        format!("\"{}\" is never closed! ({})", &s[pos..start], self.kind),
+                    )));
+                }
+            }
+        }
+
+        Some(Ok(end))
+    }
+}
+
+// モードの名前を番号にしたもの
+#[derive(Clone, Copy)]
+enum Transition {
+    Push(usize),
+    Pop,
+    Switch(usize),
+}
+
+// 字句の正規表現を一つにまとめたオートマトン．位置を固定して探すので，後ろの字句まで先読みしない
+pub struct TokenSet<T> {
+    // 位置から始まる全ての正規表現を一度に調べる
+    set: Regex,
+    // 一致した正規表現ごとに，一致の終わりを求める
+    regexes: Vec<Regex>,
+    priorities: Vec<i32>,
+    tokens: Vec<Constructor<T>>,
+}
+
+impl<T> TokenSet<T> {
+    // 字句ごとの(正規表現, 優先度, 字句を作る関数)から作る．正規表現が不正ならpanicする
+    pub fn new(patterns: Vec<(String, i32, Constructor<T>)>) -> Self {
+        let mut regexes = vec![];
+        let mut priorities = vec![];
+        let mut tokens = vec![];
+        for (pattern, priority, token) in &patterns {
+            regexes.push(Regex::new(pattern).unwrap());
+            priorities.push(*priority);
+            tokens.push(*token);
+        }
+
+        let set = Regex::builder()
+            .configure(meta::Config::new().match_kind(MatchKind::All))
+            .build_many(&patterns.iter().map(|x| &x.0).collect::<Vec<_>>())
+            .unwrap();
+
+        Self {
+            set,
+            regexes,
+            priorities,
+            tokens,
+        }
+    }
+
+    // posから始まる字句とその終わり．値に変換できなければErrに理由を入れる．
+    // 最長一致で，同じ長さなら優先度の高いもの，さらに先に宣言したものを選ぶ．空の一致は無視する
+    pub fn find_at(&self, s: &str, pos: usize) -> Option<(Result<T, String>, usize)> {
+        let (i, end) = self.find_index(s, pos)?;
+        Some(((self.tokens[i])(&s[pos..end]), end))
+    }
+
+    // 一致した字句の番号と終わり
+    fn find_index(&self, s: &str, pos: usize) -> Option<(usize, usize)> {
+        let input = Input::new(s).range(pos..).anchored(Anchored::Yes);
+
+        let mut matched = PatternSet::new(self.regexes.len());
+        self.set.which_overlapping_matches(&input, &mut matched);
+
+        // (終わり, 優先度, 宣言の逆順)が最大のもの
+        let (end, _, i) = matched
+            .iter()
+            .filter_map(|x| {
+                let i = x.as_usize();
+                let end = self.regexes[i].search(&input)?.end();
+                Some((end, self.priorities[i], std::cmp::Reverse(i)))
+            })
+            .filter(|x| x.0 > pos)
+            .max()?;
+
+        Some((i.0, end))
+    }
+}
+
+// 字句解析のモードのスタック．INITIALから始まる
+pub struct ModeStack(Vec<usize>);
+
+impl Default for ModeStack {
+    fn default() -> Self {
+        Self(vec![0])
+    }
+}
+
+impl ModeStack {
+    fn top(&self) -> usize {
+        *self.0.last().unwrap()
+    }
+}
+
+struct Mode<T> {
+    name: &'static str,
+    whitespace: WhitespaceRegex,
+    skips: Vec<Skip>,
+    tokens: TokenSet<T>,
+    transitions: Vec<Option<Transition>>,
+}
+
+// 全てのモードの字句と空白
+pub struct Lexer<T> {
+    // 先頭はINITIAL
+    modes: Vec<Mode<T>>,
+}
+
+impl<T> Lexer<T> {
+    // モードごとの空白の正規表現(先頭はINITIAL)と，読み飛ばすもの，字句から作る．
+    // 空白を指定しないモードはINITIALの空白を使う．モードや正規表現が不正ならpanicする
+    pub fn new(
+        whitespaces: Vec<(&'static str, String)>,
+        skips: Vec<SkipPattern>,
+        patterns: Vec<Pattern<T>>,
+    ) -> Self {
+        // モードは最初に現れた順に番号を振る
+        let mut names = vec![INITIAL];
+        for pattern in &patterns {
+            for name in pattern.modes {
+                if !names.contains(name) {
+                    names.push(name);
+                }
+            }
+        }
+
+        let index = |name: &str| match names.iter().position(|x| *x == name) {
+            Some(i) => i,
+            None => panic!("The mode has no token! ({})", name),
+        };
+
+        let mut whitespace_regexes = vec![None; names.len()];
+        for (name, e) in &whitespaces {
+            let regex = WhitespaceRegex::new(e).unwrap();
+            if !regex.is_match("") {
+                panic!("The empty string does not match for the WHITESPACE regex!");
+            }
+            whitespace_regexes[index(name)] = Some(regex);
+        }
+
+        let mut skip_lists: Vec<Vec<Skip>> = names.iter().map(|_| vec![]).collect();
+        for pattern in &skips {
+            for name in pattern.modes {
+                skip_lists[index(name)].push(Skip::new(pattern));
+            }
+        }
+
+        let modes = names
+            .iter()
+            .zip(skip_lists)
+            .enumerate()
+            .map(|(i, (&name, skips))| {
+                let mut tokens = vec![];
+                let mut transitions = vec![];
+                for pattern in patterns.iter().filter(|x| x.modes.contains(&name)) {
+                    tokens.push((pattern.regex.clone(), pattern.priority, pattern.constructor));
+                    transitions.push(pattern.action.map(|x| match x {
+                        Action::Push(x) => Transition::Push(index(x)),
+                        Action::Pop => Transition::Pop,
+                        Action::Switch(x) => Transition::Switch(index(x)),
+                    }));
+                }
+
+                Mode {
+                    name,
+                    whitespace: whitespace_regexes[i]
+                        .clone()
+                        .or_else(|| whitespace_regexes[0].clone())
+                        .unwrap(),
+                    skips,
+                    tokens: TokenSet::new(tokens),
+                    transitions,
+                }
+            })
+            .collect();
+
+        Self { modes }
+    }
+
+    // 今のモードでposから空白とSKIPを読み飛ばし，次の字句の始まりを返す．
+    // SKIPに一致したものはtriviaに加え，複数が一致したら最長のものを読み飛ばす．
+    // 入れ子のコメントが閉じていなければ，その始まりの範囲をErrで返す
+    pub fn skip(
+        &self,
+        stack: &ModeStack,
+        s: &str,
+        pos: usize,
+        trivia: &mut Vec<Trivia>,
+    ) -> Result<usize, (Span, String)> {
+        let mode = &self.modes[stack.top()];
+
+        let mut pos = pos;
+        loop {
+            if let Some(mat) = mode.whitespace.find_at(s, pos) {
+                if mat.start() == pos {
+                    pos = mat.end();
+                }
+            }
+
+            let mut longest: Option<(usize, &Skip)> = None;
+            for skip in &mode.skips {
+                match skip.find_at(s, pos) {
+                    Some(Ok(end)) if longest.is_none_or(|(x, _)| end > x) => {
+                        longest = Some((end, skip));
+                    }
+                    Some(Err(e)) => return Err(e),
+                    _ => {}
+                }
+            }
+
+            let Some((end, skip)) = longest else {
+                return Ok(pos);
+            };
+            trivia.push(Trivia {
+                kind: skip.kind,
+                text: s[pos..end].to_string(),
+                span: Span::new(pos, end),
+            });
+            pos = end;
+        }
+    }
+
+    // 今のモードでposの字句を読み，字句が指すモードに移る．字句とその終わりを返す．
+    // 値に変換できないときや，最後のモードを取り除くときはErrに理由を入れる
+    pub fn read(
+        &self,
+        stack: &mut ModeStack,
+        s: &str,
+        pos: usize,
+    ) -> Option<(Result<T, String>, usize)> {
+        let mode = &self.modes[stack.top()];
+        let (i, end) = mode.tokens.find_index(s, pos)?;
+
+        let token = (mode.tokens.tokens[i])(&s[pos..end]);
+        if token.is_err() {
+            return Some((token, end));
+        }
+
+        match mode.transitions[i] {
+            Some(Transition::Push(x)) => stack.0.push(x),
+            Some(Transition::Pop) if stack.0.len() == 1 => {
+                return Some((
+                    Err(format!(
+                        "\"{}\" cannot pop the last mode! ({})",
+                        &s[pos..end],
+                        mode.name
+                    )),
+                    end,
+                ));
+            }
+            Some(Transition::Pop) => {
+                stack.0.pop();
+            }
+            Some(Transition::Switch(x)) => *stack.0.last_mut().unwrap() = x,
+            None => {}
+        }
+
+        Some((token, end))
+    }
+
+    // 今のモードでposから空白，SKIP，字句のいずれかが始まるか
+    pub fn starts_at(&self, stack: &ModeStack, s: &str, pos: usize) -> bool {
+        self.skip(stack, s, pos, &mut vec![]) != Ok(pos)
+            || self.modes[stack.top()].tokens.find_index(s, pos).is_some()
+    }
+}
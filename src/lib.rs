@@ -3,106 +3,236 @@ pub use once_cell::sync::Lazy;
 pub use paste;
 pub use regex::Regex;
 
+pub mod earley;
+pub mod error;
+pub mod field;
+pub mod forest;
+pub mod glr;
+pub mod lexer;
+pub mod span;
+
 #[macro_export]
 macro_rules! syntax {
+    // SKIPは省略できる
+    (
+        WHITESPACE $tt1:tt
+        TOKEN $( $tt:tt )*
+    ) => {
+        syntax!(
+            WHITESPACE $tt1
+            SKIP {}
+            TOKEN $( $tt )*
+        );
+    };
+    (
+        WHITESPACE $tt1:tt
+        SKIP $tt6:tt
+        TOKEN $tt2:tt
+        RULE $tt3:tt
+        START { $i1:tt }
+        ALGORITHM { $i2:ident $( $i3:ident )? }
+    ) => {
+        syntax!(
+            WHITESPACE $tt1
+            SKIP $tt6
+            TOKEN $tt2
+            PRECEDENCE {}
+            RULE $tt3
+            START { $i1 }
+            ALGORITHM { $i2 $( $i3 )? }
+        );
+    };
+    (
+        WHITESPACE $tt1:tt
+        SKIP $tt6:tt
+        TOKEN $tt2:tt
+        EXPECT $tt5:tt
+        RULE $tt3:tt
+        START { $i1:tt }
+        ALGORITHM { $i2:ident $( $i3:ident )? }
+    ) => {
+        syntax!(
+            WHITESPACE $tt1
+            SKIP $tt6
+            TOKEN $tt2
+            PRECEDENCE {}
+            EXPECT $tt5
+            RULE $tt3
+            START { $i1 }
+            ALGORITHM { $i2 $( $i3 )? }
+        );
+    };
+    (
+        WHITESPACE $tt1:tt
+        SKIP $tt6:tt
+        TOKEN $tt2:tt
+        PRECEDENCE $tt4:tt
+        RULE $tt3:tt
+        START { $i1:tt }
+        ALGORITHM { $i2:ident $( $i3:ident )? }
+    ) => {
+        syntax!(
+            WHITESPACE $tt1
+            SKIP $tt6
+            TOKEN $tt2
+            PRECEDENCE $tt4
+            EXPECT {}
+            RULE $tt3
+            START { $i1 }
+            ALGORITHM { $i2 $( $i3 )? }
+        );
+    };
     (
         WHITESPACE $tt1:tt
+        SKIP $tt6:tt
         TOKEN $tt2:tt
+        PRECEDENCE $tt4:tt
+        EXPECT $tt5:tt
         RULE $tt3:tt
         START { $i1:tt }
-        ALGORITHM { $i2:ident }
+        ALGORITHM { $i2:ident $( $i3:ident )? }
     ) => {
-        declare_whitespace_regex!($tt1);
-        declare_token_extractors!($tt2);
+        declare_lexer!($tt1, $tt6, $tt2);
         impl_token!($tt2);
         impl_terminal_symbol!($tt2);
         impl_nonterminal_symbol!($tt3);
 
         impl_lex!();
-        impl_yacc!($tt2, $tt3, $i1, $i2);
+        impl_yacc!($tt2, $tt3, $tt4, $tt5, $i1, $i2, { $( $i3 )? });
 
         impl_parser!($i1);
     };
 }
 
+// Reservedは優先度1の略記
 #[macro_export]
-macro_rules! declare_whitespace_regex {
-    ( { $e:expr } ) => {
-        static WHITESPACE_REGEX: Lazy<Regex> = Lazy::new(|| {
-            let regex = Regex::new($e).unwrap();
+macro_rules! token_priority {
+    ( { $e:expr, Reserved }, ) => {
+        1
+    };
+    ( { $e:expr $( , $t:ty $( , $f:expr )? )? }, ) => {
+        0
+    };
+    ( $tt:tt, $p:literal ) => {
+        $p
+    };
+}
 
-            if !regex.is_match("") {
-                panic!("The empty string does not match for the WHITESPACE regex!");
-            }
+// 字句の値への変換に失敗したら，その理由を返す
+#[macro_export]
+macro_rules! token_constructor {
+    ( $i:ident ) => {
+        (|s: &str| $i::convert(s).map(Token::$i)) as lexer::Constructor<Token>
+    };
+}
 
-            regex
-        });
+#[macro_export]
+macro_rules! token_regex {
+    ( { $e:expr $( , $t:ty $( , $f:expr )? )? } ) => {
+        ($e).to_string()
     };
 }
 
+// モードを書かなければINITIAL
 #[macro_export]
-macro_rules! push_closure {
-    ( $ret:ident, $i:ident, { $e:expr } ) => {
-        $ret.not_reserved.push({
-            let regex = Regex::new($e).unwrap();
-            Box::new(move |pos: usize, s: &str| {
-                if let Some(mat) = regex.find_at(s, pos) {
-                    if pos == mat.start() {
-                        return Some(Token::$i($i(mat.as_str().to_string())));
-                    }
-                }
-                None
-            })
-        });
+macro_rules! token_modes {
+    () => {
+        &[lexer::INITIAL]
     };
-    ( $ret:ident, $i:ident, { $e:expr, Reserved } ) => {
-        $ret.reserved.push({
-            let regex = Regex::new($e).unwrap();
-            Box::new(move |pos: usize, s: &str| {
-                if let Some(mat) = regex.find_at(s, pos) {
-                    if pos == mat.start() {
-                        return Some(Token::$i($i(mat.as_str().to_string())));
-                    }
-                }
-                None
-            })
-        });
+    ( $( $mode:ident )+ ) => {
+        &[ $( stringify!($mode) ),+ ]
     };
 }
 
 #[macro_export]
-macro_rules! declare_token_extractors {
-    ( { $( $i:ident => $tt:tt )* } ) => {
+macro_rules! mode_action {
+    () => {
+        None
+    };
+    ( push $mode:ident ) => {
+        Some(lexer::Action::Push(stringify!($mode)))
+    };
+    ( pop ) => {
+        Some(lexer::Action::Pop)
+    };
+    ( switch $mode:ident ) => {
+        Some(lexer::Action::Switch(stringify!($mode)))
+    };
+    ( $( $tt:tt )* ) => {
+        compile_error!(concat!(
+            "The mode action must be push(M), pop or switch(M)! (",
+            stringify!($( $tt )*),
+            ")"
+        ))
+    };
+}
 
-        struct Closures {
-            reserved: Vec<Box<dyn Fn(usize, &str) -> Option<Token> + Send + Sync>>,
-            not_reserved: Vec<Box<dyn Fn(usize, &str) -> Option<Token> + Send + Sync>>,
+// 入れ子にできるコメントは始まりと終わりの正規表現で書く
+#[macro_export]
+macro_rules! skip_pattern {
+    ( $k:ident, $modes:expr, { $open:expr, $close:expr, Nested } ) => {
+        lexer::SkipPattern {
+            kind: stringify!($k),
+            modes: $modes,
+            regex: ($open).to_string(),
+            close: Some(($close).to_string()),
         }
-
-        impl Closures {
-            fn new() -> Self {
-                Self {
-                    reserved: vec![],
-                    not_reserved: vec![],
-                }
-            }
+    };
+    ( $k:ident, $modes:expr, { $e:expr } ) => {
+        lexer::SkipPattern {
+            kind: stringify!($k),
+            modes: $modes,
+            regex: ($e).to_string(),
+            close: None,
         }
+    };
+}
 
-        static TOKEN_EXTRACTORS: Lazy<Closures> = Lazy::new(|| {
-            let mut ret = Closures::new();
-
-            $(
-                push_closure!(ret, $i, $tt);
-            )*
+// 空白の正規表現はモードごとに書ける．書かなければINITIALのもの
+#[macro_export]
+macro_rules! declare_lexer {
+    (
+        { $e:expr $( , $m:ident => $e2:expr )* $(,)? },
+        { $( $k:ident $( < $( $skip_mode:ident ),+ > )? => $skip:tt )* },
+        { $( $i:ident $( $name:literal )? $( < $( $mode:ident ),+ > )? => $tt:tt $( % $p:literal )? $( -> $a:ident $( ( $target:ident ) )? )? )* }
+    ) => {
+        check_modes!(
+            { $( $( $( $mode )+ )? )* }
+            { $( $m )* $( $( $( $skip_mode )+ )? )* $( $( $( $target )? )? )* }
+        );
 
-            ret
+        static LEXER: Lazy<lexer::Lexer<Token>> = Lazy::new(|| {
+            lexer::Lexer::new(
+                vec![
+                    (lexer::INITIAL, ($e).to_string()),
+                    $(
+                        (stringify!($m), ($e2).to_string()),
+                    )*
+                ],
+                vec![
+                    $(
+                        skip_pattern!($k, token_modes!($( $( $skip_mode )+ )?), $skip),
+                    )*
+                ],
+                vec![
+                    $(
+                        lexer::Pattern {
+                            modes: token_modes!($( $( $mode )+ )?),
+                            regex: token_regex!($tt),
+                            priority: token_priority!($tt, $( $p )?),
+                            constructor: token_constructor!($i),
+                            action: mode_action!($( $a $( $target )? )?),
+                        },
+                    )*
+                ],
+            )
         });
     };
 }
 
 #[macro_export]
 macro_rules! impl_token {
-    ( { $( $i:ident => $tt:tt )* } ) => {
+    ( { $( $i:ident $( $name:literal )? $( < $( $mode:ident ),+ > )? => $tt:tt $( % $p:literal )? $( -> $a:ident $( ( $target:ident ) )? )? )* } ) => {
         #[derive(Debug, Clone)]
         enum Token {
             $(
@@ -114,47 +244,196 @@ macro_rules! impl_token {
             fn get_str(&self) -> String {
                 match self {
                 $(
-                    Token::$i($i(s)) => s.to_string(),
+                    Token::$i(x) => x.to_string(),
+                )*
+                }
+            }
+
+            fn set_span(&mut self, span: span::Span) {
+                match self {
+                $(
+                    Token::$i(x) => x.set_span(span),
+                )*
+                }
+            }
+
+            fn set_trivia(&mut self, trivia: Vec<lexer::Trivia>) {
+                match self {
+                $(
+                    Token::$i(x) => x.set_trivia(trivia),
+                )*
+                }
+            }
+
+            fn span(&self) -> span::Span {
+                match self {
+                $(
+                    Token::$i(x) => span::Spanned::span(x).unwrap(),
+                )*
+                }
+            }
+
+            fn symbol(&self) -> &'static str {
+                match self {
+                $(
+                    Token::$i(_) => stringify!($i),
                 )*
                 }
             }
+
+            // 誤りに表示する名前．名前がなければ記号そのもの
+            fn display_name(symbol: &str) -> &'static str {
+                $(
+                    if symbol == stringify!($i) {
+                        return display_name!($i $( $name )?);
+                    }
+                )*
+                "end of input"
+            }
         }
     };
 }
 
 #[macro_export]
 macro_rules! impl_terminal_symbol {
-    ( { $( $i:ident => $tt:tt )* } ) => {
+    ( { $( $i:ident $( $name:literal )? $( < $( $mode:ident ),+ > )? => $tt:tt $( % $p:literal )? $( -> $a:ident $( ( $target:ident ) )? )? )* } ) => {
         $(
-            #[derive(Debug, Clone, PartialEq, Eq)]
-            pub struct $i(String);
-            impl $i {
-                pub fn new(s: &str) -> Self {
-                    Self(s.to_string())
-                }
+            impl_terminal_symbol!($i $tt);
+        )*
+    };
+    ( $i:ident { $e:expr $( , Reserved )? } ) => {
+        #[derive(Clone)]
+        pub struct $i(String, span::Span, Vec<lexer::Trivia>);
+        impl $i {
+            pub fn new(s: &str) -> Self {
+                Self(s.to_string(), span::Span::default(), vec![])
+            }
+            fn convert(s: &str) -> Result<Self, String> {
+                Ok(Self::new(s))
+            }
+        }
+        impl std::fmt::Display for $i {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+        impl_terminal_span!($i, [0], 1, 2);
+    };
+    // 値を持つ終端記号は，変換した値と元の文字列を持つ
+    ( $i:ident { $e:expr , $t:ty $( , $f:expr )? } ) => {
+        #[derive(Clone)]
+        pub struct $i($t, String, span::Span, Vec<lexer::Trivia>);
+        impl $i {
+            pub fn new(s: &str) -> Self {
+                Self::convert(s).unwrap()
             }
-            impl std::fmt::Display for $i {
-                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                    write!(f, "{}", self.0)
+            fn convert(s: &str) -> Result<Self, String> {
+                match convert_token!(s, $t $( , $f )?) {
+                    Ok(x) => Ok(Self(x, s.to_string(), span::Span::default(), vec![])),
+                    Err(e) => Err(format!(
+                        "\"{}\" cannot be converted to {}! ({})",
+                        s,
+                        stringify!($i),
+                        e
+                    )),
                 }
             }
-        )*
+        }
+        impl std::fmt::Display for $i {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.1)
+            }
+        }
+        impl_terminal_span!($i, [0 1], 2, 3);
+    };
+}
+
+#[macro_export]
+macro_rules! display_name {
+    ( $i:ident ) => {
+        stringify!($i)
+    };
+    ( $i:ident $name:literal ) => {
+        $name
+    };
+}
+
+// 位置と前に読み飛ばしたものは等価性とDebugの表示に含めない
+#[macro_export]
+macro_rules! impl_terminal_span {
+    ( $i:ident, [ $( $n:tt )+ ], $m:tt, $l:tt ) => {
+        impl $i {
+            fn set_span(&mut self, span: span::Span) {
+                self.$m = span;
+            }
+            fn set_trivia(&mut self, trivia: Vec<lexer::Trivia>) {
+                self.$l = trivia;
+            }
+            // 字句の直前にSKIPで読み飛ばしたコメントなど
+            #[allow(dead_code)]
+            pub fn trivia(&self) -> &[lexer::Trivia] {
+                &self.$l
+            }
+        }
+        impl span::Spanned for $i {
+            fn span(&self) -> Option<span::Span> {
+                Some(self.$m)
+            }
+        }
+        impl std::cmp::PartialEq for $i {
+            fn eq(&self, other: &Self) -> bool {
+                $( self.$n == other.$n )&&+
+            }
+        }
+        impl std::cmp::Eq for $i {}
+        impl std::fmt::Debug for $i {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_tuple(stringify!($i))
+                    $( .field(&self.$n) )+
+                    .finish()
+            }
+        }
     };
 }
 
+// 変換関数がなければFromStrで変換する
 #[macro_export]
-macro_rules! impl_parsablell_for_terminal_symbol {
-    ( { $( $i:ident => $tt:tt )* } ) => {
+macro_rules! convert_token {
+    ( $s:ident, $t:ty ) => {
+        $s.parse::<$t>()
+    };
+    ( $s:ident, $t:ty, $f:expr ) => {
+        ($f)($s)
+    };
+}
+
+// 型を宣言した非終端記号は同じ型を共有し得るので，トレイトではなく記号ごとの関数にする
+#[macro_export]
+macro_rules! define_parse_ll_for_terminal_symbol {
+    ( { $( $i:ident $( $name:literal )? $( < $( $mode:ident ),+ > )? => $tt:tt $( % $p:literal )? $( -> $a:ident $( ( $target:ident ) )? )? )* } ) => {
         $(
-            impl ParsableLL for $i {
-                fn parse_ll(v: &Vec<Token>, idx: &mut usize) -> Result<Self, String> {
-                    if let Some(Token::$i(x)) = v.get(*idx) {
+            paste::item! {
+                #[allow(dead_code, non_snake_case, clippy::ptr_arg)]
+                fn [<parse_ll_ $i>](v: &Vec<Token>, idx: &mut usize, memo: &mut Memo) -> Result<std::rc::Rc<Derivation>, String> {
+                    if let Some(Token::$i(_)) = v.get(*idx) {
                         *idx += 1;
-                        return Ok(x.clone());
+                        Ok(std::rc::Rc::new(Derivation::Leaf(*idx - 1)))
                     } else {
+                        memo.fail(*idx, stringify!($i));
                         Err("ParseError!".to_string())
                     }
                 }
+
+                #[allow(dead_code, non_snake_case, clippy::ptr_arg)]
+                fn [<build_ll_ $i>](v: &Vec<Token>, d: &Derivation) -> $i {
+                    match d {
+                        Derivation::Leaf(i) => match v.get(*i) {
+                            Some(Token::$i(x)) => x.clone(),
+                            _ => unreachable!(),
+                        },
+                        Derivation::Node(..) => unreachable!(),
+                    }
+                }
             }
         )*
     };
@@ -162,170 +441,434 @@ macro_rules! impl_parsablell_for_terminal_symbol {
 
 #[macro_export]
 macro_rules! helper1 {
-    ( $i1:ident $( $i2:ident )* | $( { $( $i:ident )* } )* | $a:ident $b:ident $self:ident $f:ident ) => {
-        helper1!( $($i2)* | { $i1 $($i2)* } $( { $( $i )* } )* | $a $b $self $f )
+    ( $i1:ident $( $i2:ident )* | $( { $( $i:ident )* } )* | $( $tt:tt )* ) => {
+        helper1!( $($i2)* | { $i1 $($i2)* } $( { $( $i )* } )* | $( $tt )* )
     };
-    ( $i1:ident | $( { $( $i:ident )* } )* | $a:ident $b:ident $self:ident $f:ident ) => {
-        helper1!( | { $i1 $($i2)* } $( { $( $i )* } )* | $a $b $self $f )
+    ( | $( { $( $i:ident )* } )* | $self:ident $f:ident [ $( $arms:tt )* ] $a:ident $b:ident $( $rest:tt )* ) => {
+        helper1!( $self $f [
+            $( $arms )*
+            $a::$b( $( [<t $( _ $i:lower )* >] ),* ) => vec![
+                $( field::DisplayField::display_field([<t $( _ $i:lower )* >]) ),*
+            ],
+        ] $( $rest )* )
     };
-    ( | $( { $( $i:ident )* } )* | $a:ident $b:ident $self:ident $f:ident ) => {
-        if let $a::$b( $( paste::item!{ [<t_ $($i)* >] } ),* ) = $self {
-            return write!($f, helper2!( $( { $($i)* } )* | ), $( paste::item!{ [<t_ $($i)* >] } ),*  );
-        }
+    ( $self:ident $f:ident [ $( $arms:tt )* ] $a:ident $b:ident ( $( $i:ident )* ) $( $rest:tt )* ) => {
+        helper1!( $( $i )* | | $self $f [ $( $arms )* ] $a $b $( $rest )* )
     };
+    ( $self:ident $f:ident [ $( $arms:tt )* ] ) => {{
+        let fields: Vec<String> = paste::item! { match $self { $( $arms )* } };
+        write!($f, "{}", field::join(fields))
+    }};
 }
 
+// 欄の型．Option<X>, Vec<X>, Vec1<X>, Sep<X, S>は補助の非終端記号に展開される
 #[macro_export]
-macro_rules! helper2 {
-    ( { $($i1:ident)* } $( { $($i2:ident)* } )* | $($tt3:tt)*) => {
-        helper2!( $( { $($i2)* } )* | {} $($tt3)* )
-    };
-    ( | $($tt3:tt)* ) => {
-        stringify!($($tt3)*)
-    }
+macro_rules! field_type {
+    ( error ) => { Box<error::Recovered> };
+    ( $i:ident ) => { Box<$i> };
+    ( Option < $i:ident > ) => { Option<Box<$i>> };
+    ( Vec < $i:ident > ) => { Vec<$i> };
+    ( Vec1 < $i:ident > ) => { Vec<$i> };
+    ( Sep < $i1:ident , $i2:ident > ) => { Vec<$i1> };
 }
 
 #[macro_export]
 macro_rules! impl_nonterminal_symbol {
-    ( { $( $i1:ident => $( | $i2:ident ( $($i3:ident),* ) )+ )* } ) => {
+    ( { $( $i1:ident $( : $t:ty )? => $( | $i2:ident ( $( $i3:ident $( < $($i4:ident),+ > )? ),* ) $( % prec $p:ident )? $( => $b:tt )? )+ )* } ) => {
         $(
-            #[derive(Debug, Clone, PartialEq, Eq)]
-            pub enum $i1 {
-                $( $i2( $( Box<$i3> ),* ) ),*,
-            }
-            impl std::fmt::Display for $i1 {
-                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                    $( helper1!( $( $i3 )* | | $i1 $i2 self f); )*
-                    Err(std::fmt::Error)
-                }
-            }
+            impl_nonterminal_symbol!( $i1 [ $( $t )? ] $( $i2 ( $( $i3 $( < $($i4),+ > )? ),* ) )+ );
         )*
+        impl_spanned!( $( $i1 $( [ $t ] )? { $( $i2 ( $( $i3 $( < $($i4)+ > )? )* ) )+ } )* );
+    };
+    ( $i1:ident [] $( $i2:ident ( $( $i3:ident $( < $($i4:ident),+ > )? ),* ) )+ ) => {
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub enum $i1 {
+            $( $i2( $( field_type!($i3 $( < $($i4),+ > )?) ),* ) ),*,
+        }
+        impl std::fmt::Display for $i1 {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                helper1!( self f [] $( $i1 $i2 ( $( $i3 )* ) )* )
+            }
+        }
+    };
+    // 型を宣言した非終端記号の値は，意味動作で計算したその型の値
+    ( $i1:ident [ $t:ty ] $( $tt:tt )* ) => {
+        pub type $i1 = $t;
     };
 }
 
+// 構文解析では導出だけを作り，値は読み終えた後に一度だけ作る．覚えた導出は複製せずに共有する
 #[macro_export]
-macro_rules! impl_parsablell_for_nonterminal_symbol {
-    ( { $( $i1:ident => $( | $i2:ident ( $($tt:tt),* ) )+ )* } ) => {
+macro_rules! define_parse_ll_for_nonterminal_symbol {
+    ( { $( $i1:ident $( : $t:ty )? => $( | $i2:ident ( $( $i3:ident $( < $($i4:ident),+ > )? ),* ) $( % prec $p:ident )? $( => $b:tt )? )+ )* } ) => {
         $(
-            impl ParsableLL for $i1 {
-                fn parse_ll(v: &Vec<Token>, idx: &mut usize) -> Result<Self, String> {
+            paste::item! {
+                #[allow(non_snake_case, clippy::ptr_arg)]
+                fn [<parse_ll_ $i1>](v: &Vec<Token>, idx: &mut usize, memo: &mut Memo) -> Result<std::rc::Rc<Derivation>, String> {
                     let start_idx = *idx;
+                    let key = (stringify!($i1), start_idx);
 
-                    $(
-                        let mut c = || -> Result<Self, String> {
-                            Ok($i1::$i2(
-                                $( Box::new($tt::parse_ll(v, idx)?) ),*
-                            ))
+                    if let Some(result) = memo.$i1.get(&start_idx) {
+                        // 計算中の位置に戻ってきたなら左再帰
+                        if let Some(recursed) = memo.in_progress.get_mut(&key) {
+                            *recursed = true;
+                        }
+
+                        return if let Some((x, end_idx)) = result {
+                            *idx = *end_idx;
+                            Ok(x.clone())
+                        } else {
+                            Err("ParseError!".to_string())
                         };
-                        if let Ok(x) = c() {
-                            return Ok(x);
+                    }
+
+                    memo.$i1.insert(start_idx, None);
+                    memo.in_progress.insert(key, false);
+
+                    let eval = |idx: &mut usize, memo: &mut Memo| -> Result<std::rc::Rc<Derivation>, String> {
+                        $(
+                            let mut c = || -> Result<std::rc::Rc<Derivation>, String> {
+                                Ok(std::rc::Rc::new(Derivation::Node(
+                                    stringify!($i2),
+                                    vec![ $( parse_ll_field!(v, idx, memo, $i3 $( < $($i4),+ > )?) ),* ],
+                                )))
+                            };
+                            if let Ok(x) = c() {
+                                return Ok(x);
+                            }
+                            *idx = start_idx;
+                        )*
+
+                        Err("ParseError!".to_string())
+                    };
+
+                    let mut result = eval(idx, memo);
+
+                    // 左再帰なら，読み終える位置が伸びなくなるまで結果を種として育てる
+                    if memo.in_progress[&key] {
+                        while let Ok(x) = &result {
+                            let end_idx = *idx;
+                            memo.$i1.insert(start_idx, Some((x.clone(), end_idx)));
+                            memo.forget(start_idx);
+
+                            *idx = start_idx;
+                            match eval(idx, memo) {
+                                Ok(y) if *idx > end_idx => result = Ok(y),
+                                _ => {
+                                    *idx = end_idx;
+                                    break;
+                                }
+                            }
                         }
-                        *idx = start_idx;
-                    )*
+                    }
+
+                    memo.in_progress.remove(&key);
+
+                    if memo.packrat {
+                        memo.$i1
+                            .insert(start_idx, result.as_ref().ok().map(|x| (x.clone(), *idx)));
+                    } else {
+                        memo.$i1.remove(&start_idx);
+                    }
 
-                    Err("ParseError!".to_string())
+                    result
+                }
+
+                // 意味動作は波括弧で囲まれている
+                #[allow(non_snake_case, unused_braces, clippy::ptr_arg)]
+                fn [<build_ll_ $i1>](v: &Vec<Token>, d: &Derivation) -> $i1 {
+                    let Derivation::Node(rule, fields) = d else {
+                        unreachable!()
+                    };
+                    $(
+                        if *rule == stringify!($i2) {
+                            return construct_ll!(v, fields, $i1 $i2 ( $( $i3 $( < $($i4),+ > )? ),* ) $( $b )?);
+                        }
+                    )*
+                    unreachable!()
                 }
             }
         )*
     };
 }
 
+// 規則の値を作る．意味動作があれば右辺の値の組に対して評価し，なければ木を作る
+#[macro_export]
+macro_rules! construct_ll {
+    ( $v:ident, $fields:ident, $i1:ident $i2:ident ( $( $i3:ident $( < $($i4:ident),+ > )? ),* ) ) => {{
+        #[allow(unused_variables)]
+        let fields = &mut $fields.iter();
+        $i1::$i2(
+            $( box_field!($i3 $( < $($i4),+ > )?, build_ll_field!($v, fields.next().unwrap(), $i3 $( < $($i4),+ > )?)) ),*
+        )
+    }};
+    ( $v:ident, $fields:ident, $i1:ident $i2:ident ( $( $i3:ident $( < $($i4:ident),+ > )? ),* ) $b:tt ) => {{
+        #[allow(unused_variables)]
+        let fields = &mut $fields.iter();
+        #[allow(unused_variables)]
+        let children = ( $( build_ll_field!($v, fields.next().unwrap(), $i3 $( < $($i4),+ > )?), )* );
+        impl_action!(children $b)
+    }};
+}
+
+// 木の欄に収める．XとOption<X>の中身はBoxに包む
+#[macro_export]
+macro_rules! box_field {
+    ( $i:ident , $e:expr ) => {
+        Box::new($e)
+    };
+    ( Option < $i:ident > , $e:expr ) => {
+        $e.map(Box::new)
+    };
+    ( $i1:ident < $($i2:ident),+ > , $e:expr ) => {
+        $e
+    };
+}
+
+// 欄の導出を読む．繰り返しは読めなくなるまで読み，読めなかった分は戻す
+#[macro_export]
+macro_rules! parse_ll_field {
+    ( $v:ident, $idx:ident, $memo:ident, $i:ident ) => {
+        vec![paste::item! { [<parse_ll_ $i>]($v, $idx, $memo)? }]
+    };
+    ( $v:ident, $idx:ident, $memo:ident, Option < $i:ident > ) => {{
+        let start_idx = *$idx;
+        match paste::item! { [<parse_ll_ $i>]($v, $idx, $memo) } {
+            Ok(x) => vec![x],
+            Err(_) => {
+                *$idx = start_idx;
+                vec![]
+            }
+        }
+    }};
+    ( $v:ident, $idx:ident, $memo:ident, Vec < $i:ident > ) => {{
+        let mut ret = vec![];
+        loop {
+            let start_idx = *$idx;
+            match paste::item! { [<parse_ll_ $i>]($v, $idx, $memo) } {
+                // 空列を読み続けないよう，進まなければ終える
+                Ok(x) if *$idx > start_idx => ret.push(x),
+                _ => {
+                    *$idx = start_idx;
+                    break;
+                }
+            }
+        }
+        ret
+    }};
+    ( $v:ident, $idx:ident, $memo:ident, Vec1 < $i:ident > ) => {{
+        let mut ret = parse_ll_field!($v, $idx, $memo, $i);
+        ret.extend(parse_ll_field!($v, $idx, $memo, Vec<$i>));
+        ret
+    }};
+    ( $v:ident, $idx:ident, $memo:ident, Sep < $i1:ident , $i2:ident > ) => {{
+        let mut ret = vec![];
+        let start_idx = *$idx;
+        match paste::item! { [<parse_ll_ $i1>]($v, $idx, $memo) } {
+            Ok(x) => {
+                ret.push(x);
+                loop {
+                    let start_idx = *$idx;
+                    match paste::item! { [<parse_ll_ $i2>]($v, $idx, $memo) }
+                        .and_then(|_| paste::item! { [<parse_ll_ $i1>]($v, $idx, $memo) })
+                    {
+                        Ok(x) => ret.push(x),
+                        Err(_) => {
+                            *$idx = start_idx;
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(_) => *$idx = start_idx,
+        }
+        ret
+    }};
+}
+
+// 欄の導出から値を作る
+#[macro_export]
+macro_rules! build_ll_field {
+    ( $v:ident, $e:expr, $i:ident ) => {
+        paste::item! { [<build_ll_ $i>]($v, &$e[0]) }
+    };
+    ( $v:ident, $e:expr, Option < $i:ident > ) => {
+        $e.first().map(|d| paste::item! { [<build_ll_ $i>]($v, d) })
+    };
+    ( $v:ident, $e:expr, Sep < $i1:ident , $i2:ident > ) => {
+        build_ll_field!($v, $e, Vec<$i1>)
+    };
+    ( $v:ident, $e:expr, $i1:ident < $i2:ident > ) => {
+        $e.iter()
+            .map(|d| paste::item! { [<build_ll_ $i2>]($v, d) })
+            .collect::<Vec<_>>()
+    };
+}
+
 #[macro_export]
 macro_rules! impl_lex {
     () => {
         struct Lex;
 
         impl Lex {
-            fn tokenize(s: &str) -> Result<Vec<Token>, String> {
-                let mut ret = vec![];
+            fn tokenize(s: &str) -> Result<Vec<Token>, error::ParseError> {
+                let (ret, mut errors) = Self::run(s, false);
+                if errors.is_empty() {
+                    Ok(ret)
+                } else {
+                    Err(errors.remove(0))
+                }
+            }
+
+            // 読めない文字を誤りとして記録し，次に字句が始まる位置まで飛ばして続ける．
+            // 読めた字句の列と全ての誤りを返す
+            #[allow(dead_code)]
+            fn tokenize_recover(s: &str) -> (Vec<Token>, Vec<error::ParseError>) {
+                Self::run(s, true)
+            }
 
-                let mut current_pos = 0;
+            fn run(s: &str, recover: bool) -> (Vec<Token>, Vec<error::ParseError>) {
+                let mut ret = vec![];
+                let mut errors = vec![];
 
-                Self::skip(&s, &mut current_pos);
+                let mut modes = lexer::ModeStack::default();
+                // 次の字句の前に読み飛ばしたもの
+                let mut trivia = vec![];
+                let mut current_pos = Self::skip(&modes, s, 0, &mut trivia, &mut errors);
 
                 while current_pos < s.len() {
-                    if let Some(token) = Self::find_and_split(&s, &mut current_pos) {
-                        ret.push(token);
-                    } else {
-                        return Err(format!(
-                            "TokenizeError at Col {}: \"{}\"",
-                            current_pos,
-                            &s[current_pos..std::cmp::min(current_pos + 30, s.len())]
-                        ));
+                    match LEXER.read(&mut modes, s, current_pos) {
+                        Some((Ok(mut token), end)) => {
+                            token.set_span(span::Span::new(current_pos, end));
+                            token.set_trivia(std::mem::take(&mut trivia));
+                            ret.push(token);
+                            current_pos = end;
+                        }
+                        Some((Err(message), end)) => {
+                            // 値に変換できなかった字句は読み飛ばす
+                            let span = span::Span::new(current_pos, end);
+                            errors.push(Self::error(s, span, message));
+                            current_pos = end;
+                        }
+                        None => {
+                            let end = Self::find_next_start(&modes, s, current_pos);
+                            let message = format!(
+                                "\"{}\"",
+                                s[current_pos..].chars().take(30).collect::<String>()
+                            );
+                            errors.push(Self::error(s, span::Span::new(current_pos, end), message));
+                            current_pos = end;
+                        }
+                    }
+
+                    if !recover && !errors.is_empty() {
+                        break;
                     }
 
-                    Self::skip(&s, &mut current_pos);
+                    current_pos = Self::skip(&modes, s, current_pos, &mut trivia, &mut errors);
                 }
 
-                Ok(ret)
+                (ret, errors)
             }
 
-            fn find_and_split(s: &str, current_pos: &mut usize) -> Option<Token> {
-                if let Some(token) = Self::find_not_reserved(s, current_pos) {
-                    let word = token.get_str();
-                    *current_pos += word.len();
-                    if let Some(token_reserved) = Self::is_match_with_reserved(&word) {
-                        Some(token_reserved)
-                    } else {
-                        Some(token)
+            // 閉じていないコメントは誤りとして，入力の末尾まで読み飛ばす
+            fn skip(
+                modes: &lexer::ModeStack,
+                s: &str,
+                pos: usize,
+                trivia: &mut Vec<lexer::Trivia>,
+                errors: &mut Vec<error::ParseError>,
+            ) -> usize {
+                match LEXER.skip(modes, s, pos, trivia) {
+                    Ok(x) => x,
+                    Err((span, message)) => {
+                        errors.push(Self::error(s, span, message));
+                        s.len()
                     }
-                } else if let Some(token_reserved) = Self::find_reserved(s, current_pos) {
-                    let word = token_reserved.get_str();
-                    *current_pos += word.len();
-                    Some(token_reserved)
-                } else {
-                    None
                 }
             }
 
-            fn find_not_reserved(s: &str, current_pos: &usize) -> Option<Token> {
-                for closure in TOKEN_EXTRACTORS.not_reserved.iter() {
-                    if let Some(token) = closure(*current_pos, s) {
-                        return Some(token);
-                    }
+            fn error(s: &str, span: span::Span, message: String) -> error::ParseError {
+                let (line, col) = span.line_col(s);
+                error::ParseError::LexError {
+                    span,
+                    line,
+                    col,
+                    message,
                 }
+            }
 
-                None
+            // posより後で，字句か空白が始まる最初の位置．なければ入力の末尾．
+            // 文字の途中で切らないよう，文字単位で探す
+            fn find_next_start(modes: &lexer::ModeStack, s: &str, pos: usize) -> usize {
+                s[pos..]
+                    .char_indices()
+                    .skip(1)
+                    .map(|(i, _)| pos + i)
+                    .find(|&x| LEXER.starts_at(modes, s, x))
+                    .unwrap_or(s.len())
             }
+        }
+    };
+}
 
-            fn find_reserved(s: &str, current_pos: &usize) -> Option<Token> {
-                for closure in TOKEN_EXTRACTORS.reserved.iter() {
-                    if let Some(token) = closure(*current_pos, s) {
-                        return Some(token);
-                    }
-                }
+#[macro_export]
+macro_rules! define_memo {
+    ( { $( $i1:ident $( : $t:ty )? => $( | $i2:ident ( $( $i3:ident $( < $($i4:ident),+ > )? ),* ) $( % prec $p:ident )? $( => $b:tt )? )+ )* } ) => {
+        // 読んだ記号の導出
+        enum Derivation {
+            // 字句の位置
+            Leaf(usize),
+            // 規則の名前と，欄ごとの導出
+            Node(&'static str, Vec<Vec<std::rc::Rc<Derivation>>>),
+        }
 
-                None
-            }
+        // LLでは計算中の左再帰の種だけを，Packratでは全ての結果を覚えておく
+        #[allow(non_snake_case)]
+        struct Memo {
+            packrat: bool,
+            // 字句を読めなかった最も遠い位置と，そこで読もうとした字句
+            furthest: usize,
+            expected: Vec<&'static str>,
+            // Map<(非終端記号, 位置), 左再帰を検出したか>
+            in_progress: std::collections::HashMap<(&'static str, usize), bool>,
+            // Map<位置, その位置から読んだ導出と読み終えた位置>
+            $(
+                $i1: std::collections::HashMap<usize, Option<(std::rc::Rc<Derivation>, usize)>>,
+            )*
+        }
 
-            fn is_match_with_reserved(word: &str) -> Option<Token> {
-                for closure in TOKEN_EXTRACTORS.reserved.iter() {
-                    if let Some(token) = closure(0, &word) {
-                        if word == token.get_str() {
-                            return Some(token);
-                        }
-                    }
+        impl Memo {
+            fn new(packrat: bool) -> Self {
+                Self {
+                    packrat,
+                    furthest: 0,
+                    expected: vec![],
+                    in_progress: std::collections::HashMap::new(),
+                    $(
+                        $i1: std::collections::HashMap::new(),
+                    )*
                 }
-
-                None
             }
 
-            fn skip(s: &str, current_pos: &mut usize) {
-                if let Some(mat) = WHITESPACE_REGEX.find_at(&s, *current_pos) {
-                    if *current_pos == mat.start() {
-                        *current_pos = mat.end();
-                    }
+            fn fail(&mut self, idx: usize, symbol: &'static str) {
+                if idx > self.furthest {
+                    self.furthest = idx;
+                    self.expected.clear();
+                }
+                if idx == self.furthest {
+                    self.expected.push(symbol);
                 }
             }
-        }
-    };
-}
 
-#[macro_export]
-macro_rules! define_parsablell {
-    () => {
-        trait ParsableLL: Sized {
-            fn parse_ll(v: &Vec<Token>, idx: &mut usize) -> Result<Self, String>;
+            // 種が育つと，その位置で計算済みの結果は古くなる
+            fn forget(&mut self, pos: usize) {
+                $(
+                    if !self.in_progress.contains_key(&(stringify!($i1), pos)) {
+                        self.$i1.remove(&pos);
+                    }
+                )*
+            }
         }
     };
 }
@@ -334,20 +877,74 @@ macro_rules! define_parsablell {
 macro_rules! define_yacc {
     () => {
         struct Yacc;
+
+        #[allow(clippy::ptr_arg)]
+        impl Yacc {
+            // v[idx]で構文解析に失敗したときの誤り．expectedはそこで読めた記号で，F_は入力の終わり．
+            // stateはLRオートマトンの状態
+            fn error(
+                s: &str,
+                v: &Vec<Token>,
+                idx: usize,
+                expected: &[&str],
+                state: Option<usize>,
+            ) -> error::ParseError {
+                let mut expected: Vec<_> =
+                    expected.iter().map(|x| Token::display_name(x)).collect();
+                expected.sort();
+                expected.dedup();
+
+                match v.get(idx) {
+                    Some(token) => {
+                        let span = token.span();
+                        let (line, col) = span.line_col(s);
+                        error::ParseError::UnexpectedToken {
+                            span,
+                            line,
+                            col,
+                            token: token.symbol(),
+                            text: token.get_str(),
+                            expected,
+                            state,
+                        }
+                    }
+                    None => {
+                        let span = span::Span::new(s.len(), s.len());
+                        let (line, col) = span.line_col(s);
+                        error::ParseError::UnexpectedEof {
+                            span,
+                            line,
+                            col,
+                            expected,
+                            state,
+                        }
+                    }
+                }
+            }
+        }
     };
 }
 
 #[macro_export]
 macro_rules! impl_parser_ll {
-    ( $i:ident ) => {
+    ( $i:ident, $packrat:expr ) => {
+        #[allow(clippy::ptr_arg)]
         impl Yacc {
-            fn parse(v: &Vec<Token>) -> Result<$i, String> {
+            // 後戻りするので，最も遠くまで読めた位置を誤りの位置とする
+            fn parse(s: &str, v: &Vec<Token>) -> Result<$i, error::ParseError> {
+                Self::check_grammar()?;
+
                 let mut idx = 0;
-                let result = $i::parse_ll(&v, &mut idx)?;
-                if idx == v.len() {
-                    Ok(result)
-                } else {
-                    Err("ParseError!".to_string())
+                let mut memo = Memo::new($packrat);
+                match paste::item! { [<parse_ll_ $i>](&v, &mut idx, &mut memo) } {
+                    Ok(d) if idx == v.len() => Ok(paste::item! { [<build_ll_ $i>](v, &d) }),
+                    result => {
+                        // 読み終えた後に余りがあれば，そこでは入力の終わりも読めた
+                        if result.is_ok() {
+                            memo.fail(idx, "F_");
+                        }
+                        Err(Self::error(s, v, memo.furthest, &memo.expected, None))
+                    }
                 }
             }
         }
@@ -355,18 +952,53 @@ macro_rules! impl_parser_ll {
 }
 
 #[macro_export]
-macro_rules! impl_yacc {
-    ( $tt1:tt , $tt2:tt , $i1:ident , LL ) => {
-        impl_parsablell_for_terminal_symbol!($tt1);
-        impl_parsablell_for_nonterminal_symbol!($tt2);
-        define_parsablell!();
+macro_rules! impl_yacc_ll {
+    ( $tt1:tt , $tt2:tt , $i1:ident , $packrat:expr ) => {
+        define_parse_ll_for_terminal_symbol!($tt1);
+        define_parse_ll_for_nonterminal_symbol!($tt2);
+        define_memo!($tt2);
         define_yacc!();
-        impl_parser_ll!($i1);
+        impl_parser_ll!($i1, $packrat);
+    };
+}
+
+// LLとPackratのパーサはここで作り，文法はLRと同じくimpl_lr_parserで調べる
+#[macro_export]
+macro_rules! impl_yacc {
+    ( $tt1:tt , $tt2:tt , $tt3:tt , $tt4:tt , $i1:ident , LL , $tt5:tt ) => {
+        impl_yacc_ll!($tt1, $tt2, $i1, false);
+        impl_yacc_lr!($tt1, $tt2, $tt3, $tt4, $i1, LL, $tt5);
     };
 
-    ( { $( $i1:ident => $tt1:tt )* } , { $( $i2:ident => $( | $i3:ident ( $($tt2:tt),* ) )+ )* } , $i4:ident , $i5:ident ) => {
+    ( $tt1:tt , $tt2:tt , $tt3:tt , $tt4:tt , $i1:ident , Packrat , $tt5:tt ) => {
+        impl_yacc_ll!($tt1, $tt2, $i1, true);
+        impl_yacc_lr!($tt1, $tt2, $tt3, $tt4, $i1, Packrat, $tt5);
+    };
+
+    ( $tt1:tt , $tt2:tt , $tt3:tt , $tt4:tt , $i1:ident , $i2:ident , $tt5:tt ) => {
         define_yacc!();
-        impl_lr_parser!( $i5 $i4 { $( $i1 )* } { $( { $i2 $( { $i3 ( $( $tt2 )* ) } )* } )* } );
+        impl_yacc_lr!($tt1, $tt2, $tt3, $tt4, $i1, $i2, $tt5);
+    };
+}
+
+#[macro_export]
+macro_rules! impl_yacc_lr {
+    (
+        { $( $i1:ident $( $name:literal )? $( < $( $mode:ident ),+ > )? => $tt1:tt $( % $p:literal )? $( -> $a:ident $( ( $target:ident ) )? )? )* } ,
+        { $( $i2:ident $( : $t:ty )? => $( | $i3:ident ( $( $i10:ident $( < $($i11:ident),+ > )? ),* ) $( % prec $i6:ident )? $( => $b:tt )? )+ )* } ,
+        { $( $i7:ident $( $i8:ident )+ );* $(;)? } ,
+        { $( $i9:ident $l:literal );* $(;)? } ,
+        $i4:ident ,
+        $i5:ident ,
+        $tt3:tt
+    ) => {
+        impl_lr_parser!(
+            $i5 $i4 { $( $i1 )* }
+            { $( { $i2 $( [ $t ] )? $( { $i3 ( $( $i10 $( < $($i11)+ > )? )* ) $( $i6 )? $( $b )? } )* } )* }
+            { $( { $i7 $( $i8 )+ } )* }
+            { $( $i9 $l )* }
+            $tt3
+        );
     };
 }
 
@@ -375,9 +1007,9 @@ macro_rules! impl_parser {
     ( $i:ident ) => {
         pub struct Parser;
         impl Parser {
-            pub fn parse(s: &str) -> Result<$i, String> {
+            pub fn parse(s: &str) -> Result<$i, error::ParseError> {
                 let v = Lex::tokenize(s)?;
-                Yacc::parse(&v)
+                Yacc::parse(s, &v)
             }
         }
     };
@@ -625,4 +1257,2197 @@ mod tests {
         assert_eq!(result, expected);
         assert_eq!(format!("{}", result), "x + 2 + y = 4 + z".to_string());
     }
+
+    #[test]
+    fn test_lalr() {
+        use crate::*;
+
+        #[allow(dead_code)]
+        mod slr {
+            use crate::*;
+
+            syntax!(
+                WHITESPACE {
+                    r"[ \n\r\t]*"
+                }
+
+                TOKEN {
+                    Eq => {"="}
+                    Star => {r"\*"}
+                    Id => {"[a-z]+"}
+                }
+
+                RULE {
+                    S =>
+                        | S0(L, Eq, R)
+                        | S1(R)
+
+                    L =>
+                        | L0(Star, R)
+                        | L1(Id)
+
+                    R =>
+                        | R0(L)
+                }
+
+                START {
+                    S
+                }
+
+                ALGORITHM {
+                    SLR Lazy
+                }
+            );
+        }
+
+        // FOLLOW(R)が"="を含むため，SLRではshift/reduce衝突になる
+        assert!(slr::Parser::parse("*x=y").is_err());
+
+        syntax!(
+            WHITESPACE {
+                r"[ \n\r\t]*"
+            }
+
+            TOKEN {
+                Eq => {"="}
+                Star => {r"\*"}
+                Id => {"[a-z]+"}
+            }
+
+            RULE {
+                S =>
+                    | S0(L, Eq, R)
+                    | S1(R)
+
+                L =>
+                    | L0(Star, R)
+                    | L1(Id)
+
+                R =>
+                    | R0(L)
+            }
+
+            START {
+                S
+            }
+
+            ALGORITHM {
+                LALR
+            }
+        );
+
+        let s = "*x=y";
+        let result = Parser::parse(s).unwrap();
+        let l_x = L::L1(Box::new(Id::new("x")));
+        let l_sx = L::L0(Box::new(Star::new("*")), Box::new(R::R0(Box::new(l_x))));
+        let l_y = L::L1(Box::new(Id::new("y")));
+        let expected = S::S0(
+            Box::new(l_sx),
+            Box::new(Eq::new("=")),
+            Box::new(R::R0(Box::new(l_y))),
+        );
+
+        assert_eq!(result, expected);
+        assert_eq!(format!("{}", result), "* x = y".to_string());
+    }
+
+    #[test]
+    fn test_pgm() {
+        use crate::*;
+
+        #[allow(dead_code)]
+        mod lalr {
+            use crate::*;
+
+            syntax!(
+                WHITESPACE {
+                    r"[ \n\r\t]*"
+                }
+
+                TOKEN {
+                    A => {"a"}
+                    B => {"b"}
+                    C => {"c"}
+                    D => {"d"}
+                    X => {"x"}
+                }
+
+                RULE {
+                    S =>
+                        | S0(A, E, C)
+                        | S1(A, F, D)
+                        | S2(B, F, C)
+                        | S3(B, E, D)
+
+                    E =>
+                        | E0(X)
+
+                    F =>
+                        | F0(X)
+                }
+
+                START {
+                    S
+                }
+
+                ALGORITHM {
+                    LALR Lazy
+                }
+            );
+        }
+
+        // E -> x. と F -> x. の状態を併合するため，LALRではreduce/reduce衝突になる
+        assert!(lalr::Parser::parse("axd").is_err());
+        let (result, errors) = lalr::Parser::parse_recover("axd");
+        assert!(result.is_none());
+        assert_eq!(errors, vec![lalr::Parser::parse("axd").unwrap_err()]);
+
+        syntax!(
+            WHITESPACE {
+                r"[ \n\r\t]*"
+            }
+
+            TOKEN {
+                A => {"a"}
+                B => {"b"}
+                C => {"c"}
+                D => {"d"}
+                X => {"x"}
+            }
+
+            RULE {
+                S =>
+                    | S0(A, E, C)
+                    | S1(A, F, D)
+                    | S2(B, F, C)
+                    | S3(B, E, D)
+
+                E =>
+                    | E0(X)
+
+                F =>
+                    | F0(X)
+            }
+
+            START {
+                S
+            }
+
+            ALGORITHM {
+                PGM
+            }
+        );
+
+        let s = "axd";
+        let result = Parser::parse(s).unwrap();
+        let expected = S::S1(
+            Box::new(A::new("a")),
+            Box::new(F::F0(Box::new(X::new("x")))),
+            Box::new(D::new("d")),
+        );
+
+        assert_eq!(result, expected);
+        assert_eq!(format!("{}", result), "a x d".to_string());
+
+        let s = "bxc";
+        let result = Parser::parse(s).unwrap();
+        let expected = S::S2(
+            Box::new(B::new("b")),
+            Box::new(F::F0(Box::new(X::new("x")))),
+            Box::new(C::new("c")),
+        );
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_glr() {
+        use crate::*;
+
+        syntax!(
+            WHITESPACE {
+                r"[ \n\r\t]*"
+            }
+
+            TOKEN {
+                P => {r"\+"}
+                N => {"[1-9][0-9]*"}
+            }
+
+            RULE {
+                E =>
+                    | E0(E, P, E)
+                    | E1(N)
+            }
+
+            START {
+                E
+            }
+
+            ALGORITHM {
+                GLR
+            }
+        );
+
+        let s = "1+2";
+        let result = Parser::parse(s).unwrap();
+        let expected = E::E0(
+            Box::new(E::E1(Box::new(N::new("1")))),
+            Box::new(P::new("+")),
+            Box::new(E::E1(Box::new(N::new("2")))),
+        );
+
+        assert_eq!(result, expected);
+
+        let s = "1+2+3";
+        let e_1p2 = E::E0(
+            Box::new(E::E1(Box::new(N::new("1")))),
+            Box::new(P::new("+")),
+            Box::new(E::E1(Box::new(N::new("2")))),
+        );
+        let e_2p3 = E::E0(
+            Box::new(E::E1(Box::new(N::new("2")))),
+            Box::new(P::new("+")),
+            Box::new(E::E1(Box::new(N::new("3")))),
+        );
+        let left = E::E0(
+            Box::new(e_1p2),
+            Box::new(P::new("+")),
+            Box::new(E::E1(Box::new(N::new("3")))),
+        );
+        let right = E::E0(
+            Box::new(E::E1(Box::new(N::new("1")))),
+            Box::new(P::new("+")),
+            Box::new(e_2p3),
+        );
+
+        assert!(Parser::parse(s).is_err());
+
+        let result = Parser::parse_all(s).unwrap();
+        assert_eq!(result.len(), 2);
+        assert!(result.contains(&left));
+        assert!(result.contains(&right));
+
+        // 右の子が数である方を選んで左結合にする
+        let result = Parser::parse_with(s, |ambiguity| match ambiguity {
+            Ambiguity::E(v) => v
+                .iter()
+                .position(|e| matches!(e, E::E0(_, _, r) if matches!(**r, E::E1(_))))
+                .unwrap(),
+        })
+        .unwrap();
+
+        assert_eq!(result, left);
+        assert_eq!(format!("{}", result), "1 + 2 + 3".to_string());
+
+        // 候補にない番号を選ぶと木は作れない
+        assert_eq!(
+            Parser::parse_with(s, |_| 2),
+            Err(error::ParseError::Ambiguous)
+        );
+
+        // 5個の数の結合の仕方はカタラン数の14通り
+        assert_eq!(Parser::parse_all("1+2+3+4+5").unwrap().len(), 14);
+
+        // 木は求めた分だけ作るので，数えきれないほど曖昧でも先頭は取り出せる
+        let s = vec!["1"; 30].join("+");
+        let forest = Parser::parse_forest(&s).unwrap();
+        assert!(forest.is_ambiguous());
+
+        let trees: Vec<_> = forest.trees().take(3).collect();
+        assert_eq!(trees.len(), 3);
+        assert!(trees
+            .iter()
+            .all(|e| e.to_string() == vec!["1"; 30].join(" + ")));
+    }
+
+    #[test]
+    fn test_glr_long() {
+        #[allow(dead_code)]
+        mod list {
+            use crate::*;
+
+            syntax!(
+                WHITESPACE {
+                    r"[ \n\r\t]*"
+                }
+
+                TOKEN {
+                    X => {"x"}
+                }
+
+                RULE {
+                    S =>
+                        | S0(Vec<X>)
+                }
+
+                START {
+                    S
+                }
+
+                ALGORITHM {
+                    GLR
+                }
+            );
+        }
+
+        // 曖昧でない文法では，還元は新しい辺を通るものだけを行い，木は複製せずに作る
+        let s = "x ".repeat(5000);
+        let list::S::S0(v) = list::Parser::parse(&s).unwrap();
+        assert_eq!(v.len(), 5000);
+    }
+
+    #[test]
+    fn test_forest() {
+        use crate::forest::Forest;
+
+        // 文法の検査で循環は除かれるが，森は循環していてもよい．
+        // R -> A B, A -> B | y, B -> A | x
+        let mut forest = Forest::new();
+        let (x, y) = (forest.leaf(0), forest.leaf(1));
+        let (r, a, b) = (
+            forest.symbol(0, 0, 2),
+            forest.symbol(1, 0, 1),
+            forest.symbol(2, 1, 2),
+        );
+        forest.add_alternative(r, 0, vec![a, b]);
+        forest.add_alternative(a, 1, vec![b]);
+        forest.add_alternative(a, 2, vec![y]);
+        forest.add_alternative(b, 3, vec![a]);
+        forest.add_alternative(b, 4, vec![x]);
+
+        let leaf = |i: usize| ["x", "y"][i].to_string();
+        let reduce = |rule: usize, children: Vec<String>| {
+            format!(
+                "{}({})",
+                ["R", "A", "A", "B", "B"][rule],
+                children.join(" ")
+            )
+        };
+
+        // 祖先と同じ節点は使わない．どちらの子から辿っても結果は変わらない
+        let trees: Vec<_> = forest.trees(r, leaf, reduce).collect();
+        assert_eq!(
+            trees,
+            vec![
+                "R(A(B(x)) B(A(y)))",
+                "R(A(B(x)) B(x))",
+                "R(A(y) B(A(y)))",
+                "R(A(y) B(x))",
+            ]
+        );
+
+        let last = forest.tree(r, &leaf, &reduce, &|v| v.len() - 1);
+        assert_eq!(last.unwrap(), "R(A(y) B(x))");
+        assert!(forest.is_ambiguous(r));
+    }
+
+    #[test]
+    fn test_earley() {
+        use crate::*;
+
+        syntax!(
+            WHITESPACE {
+                r"[ \n\r\t]*"
+            }
+
+            TOKEN {
+                P => {r"\+"}
+                N => {"[1-9][0-9]*"}
+            }
+
+            RULE {
+                L =>
+                    | L0(L, E)
+                    | L1()
+
+                E =>
+                    | E0(E, P, E)
+                    | E1(N)
+            }
+
+            START {
+                L
+            }
+
+            ALGORITHM {
+                Earley
+            }
+        );
+
+        let s = "";
+        let result = Parser::parse(s).unwrap();
+
+        assert_eq!(result, L::L1());
+
+        let s = "1+2 3";
+        let result = Parser::parse(s).unwrap();
+        let e_1p2 = E::E0(
+            Box::new(E::E1(Box::new(N::new("1")))),
+            Box::new(P::new("+")),
+            Box::new(E::E1(Box::new(N::new("2")))),
+        );
+        let expected = L::L0(
+            Box::new(L::L0(Box::new(L::L1()), Box::new(e_1p2))),
+            Box::new(E::E1(Box::new(N::new("3")))),
+        );
+
+        assert_eq!(result, expected);
+
+        let s = "1+2+3";
+
+        assert!(Parser::parse(s).is_err());
+        assert_eq!(Parser::parse_all(s).unwrap().len(), 2);
+        assert_eq!(Parser::parse_all("1+2+3+4+5").unwrap().len(), 14);
+
+        // 左の子が数である方を選んで右結合にする
+        let result = Parser::parse_with(s, |ambiguity| match ambiguity {
+            Ambiguity::E(v) => v
+                .iter()
+                .position(|e| matches!(e, E::E0(l, _, _) if matches!(**l, E::E1(_))))
+                .unwrap(),
+            Ambiguity::L(_) => 0,
+        })
+        .unwrap();
+
+        let e_2p3 = E::E0(
+            Box::new(E::E1(Box::new(N::new("2")))),
+            Box::new(P::new("+")),
+            Box::new(E::E1(Box::new(N::new("3")))),
+        );
+        let expected = L::L0(
+            Box::new(L::L1()),
+            Box::new(E::E0(
+                Box::new(E::E1(Box::new(N::new("1")))),
+                Box::new(P::new("+")),
+                Box::new(e_2p3),
+            )),
+        );
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_earley_long() {
+        #[allow(dead_code)]
+        mod list {
+            use crate::*;
+
+            syntax!(
+                WHITESPACE {
+                    r"[ \n\r\t]*"
+                }
+
+                TOKEN {
+                    L => {r"\("}
+                    R => {r"\)"}
+                    X => {"x"}
+                }
+
+                RULE {
+                    S =>
+                        | S0(Vec<A>)
+
+                    A =>
+                        | A0(L, Vec<X>, R)
+                        | A1(X)
+                }
+
+                START {
+                    S
+                }
+
+                ALGORITHM {
+                    Earley
+                }
+            );
+        }
+
+        // 右再帰の完了は一本道を一度に登るので，長いリストも線形に読める
+        let s = "x ".repeat(5000);
+        let list::S::S0(v) = list::Parser::parse(&s).unwrap();
+        assert_eq!(v.len(), 5000);
+
+        let s = "(x x x) x ".repeat(500);
+        let list::S::S0(v) = list::Parser::parse(&s).unwrap();
+        assert_eq!(v.len(), 1000);
+        assert!(matches!(&v[0], list::A::A0(_, w, _) if w.len() == 3));
+        assert!(matches!(&v[999], list::A::A1(_)));
+    }
+
+    #[test]
+    fn test_packrat() {
+        use crate::*;
+
+        syntax!(
+            WHITESPACE {
+                r"[ \n\r\t]*"
+            }
+
+            TOKEN {
+                L => {r"\("}
+                R => {r"\)"}
+                Op => {r"\+"}
+                Num => {r"[1-9][0-9]*"}
+            }
+
+            RULE {
+                Term =>
+                    | Term0(Atom, Op, Term)
+                    | Term1(Atom)
+
+                Atom =>
+                    | Atom0(L, Term, R)
+                    | Atom1(Num)
+            }
+
+            START {
+                Term
+            }
+
+            ALGORITHM {
+                Packrat
+            }
+        );
+
+        // LLでは括弧の深さに対して指数時間かかる
+        let depth = 40;
+        let s = format!("{}1+2{}", "(".repeat(depth), ")".repeat(depth));
+        let result = Parser::parse(&s).unwrap();
+
+        let mut expected = Term::Term0(
+            Box::new(Atom::Atom1(Box::new(Num::new("1")))),
+            Box::new(Op::new("+")),
+            Box::new(Term::Term1(Box::new(Atom::Atom1(Box::new(Num::new("2")))))),
+        );
+        for _ in 0..depth {
+            expected = Term::Term1(Box::new(Atom::Atom0(
+                Box::new(L::new("(")),
+                Box::new(expected),
+                Box::new(R::new(")")),
+            )));
+        }
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_packrat_clones() {
+        use crate::*;
+        use std::cell::Cell;
+
+        thread_local! {
+            static CLONES: Cell<usize> = const { Cell::new(0) };
+        }
+
+        // 複製された回数を数える値
+        #[derive(Debug, PartialEq, Eq)]
+        struct Counted;
+
+        impl Clone for Counted {
+            fn clone(&self) -> Self {
+                CLONES.with(|x| x.set(x.get() + 1));
+                Counted
+            }
+        }
+
+        impl std::str::FromStr for Counted {
+            type Err = String;
+
+            fn from_str(_: &str) -> Result<Self, String> {
+                Ok(Counted)
+            }
+        }
+
+        syntax!(
+            WHITESPACE {
+                r"[ \n\r\t]*"
+            }
+
+            TOKEN {
+                N => {"[0-9]+", Counted}
+                P => {r"\+"}
+                Semi => {";"}
+            }
+
+            RULE {
+                S =>
+                    | S0(E, Semi)
+                    | S1(E)
+
+                E =>
+                    | Add(E, P, N)
+                    | Num(N)
+            }
+
+            START {
+                S
+            }
+
+            ALGORITHM {
+                Packrat
+            }
+        );
+
+        // 左再帰の種を育てるときも，覚えたEを読み直すときも木を複製しないので，
+        // 字句の値は木を作るときに一度ずつだけ複製される
+        for n in [100, 200, 400] {
+            let s = vec!["1"; n].join("+");
+            CLONES.with(|x| x.set(0));
+            assert!(Parser::parse(&s).is_ok());
+            assert_eq!(CLONES.with(|x| x.get()), n);
+        }
+
+        let expected = S::S1(Box::new(E::Num(Box::new(N::new("1")))));
+        assert_eq!(Parser::parse("1"), Ok(expected));
+    }
+
+    #[test]
+    fn test_ll1() {
+        use crate::*;
+
+        syntax!(
+            WHITESPACE {
+                r"[ \n\r\t]*"
+            }
+
+            TOKEN {
+                L => {r"\("}
+                R => {r"\)"}
+                P => {r"\+"}
+                N => {r"[1-9][0-9]*"}
+            }
+
+            RULE {
+                E =>
+                    | E0(T, Et)
+
+                Et =>
+                    | Et0(P, T, Et)
+                    | Et1()
+
+                T =>
+                    | T0(N)
+                    | T1(L, E, R)
+            }
+
+            START {
+                E
+            }
+
+            ALGORITHM {
+                LL1
+            }
+        );
+
+        let s = "1+(2+3)";
+        let result = Parser::parse(s).unwrap();
+        let e_2p3 = E::E0(
+            Box::new(T::T0(Box::new(N::new("2")))),
+            Box::new(Et::Et0(
+                Box::new(P::new("+")),
+                Box::new(T::T0(Box::new(N::new("3")))),
+                Box::new(Et::Et1()),
+            )),
+        );
+        let expected = E::E0(
+            Box::new(T::T0(Box::new(N::new("1")))),
+            Box::new(Et::Et0(
+                Box::new(P::new("+")),
+                Box::new(T::T1(
+                    Box::new(L::new("(")),
+                    Box::new(e_2p3),
+                    Box::new(R::new(")")),
+                )),
+                Box::new(Et::Et1()),
+            )),
+        );
+
+        assert_eq!(result, expected);
+        assert!(Parser::parse("1+(2+3").is_err());
+        assert!(Parser::parse("1+2)").is_err());
+    }
+
+    #[test]
+    fn test_ll1_long() {
+        #[allow(dead_code)]
+        mod list {
+            use crate::*;
+
+            syntax!(
+                WHITESPACE {
+                    r"[ \n\r\t]*"
+                }
+
+                TOKEN {
+                    X => {"x"}
+                }
+
+                RULE {
+                    S =>
+                        | S0(Vec<X>)
+                }
+
+                START {
+                    S
+                }
+
+                ALGORITHM {
+                    LL1
+                }
+            );
+        }
+
+        // 予測表はスタックで引くので，入力の長さによらず再帰は深くならない
+        let s = "x ".repeat(200000);
+        let list::S::S0(v) = list::Parser::parse(&s).unwrap();
+        assert_eq!(v.len(), 200000);
+    }
+
+    #[test]
+    fn test_ll1_conflicts() {
+        use crate::*;
+
+        #[allow(dead_code)]
+        mod first_follow {
+            use crate::*;
+
+            syntax!(
+                WHITESPACE {
+                    r"[ \n\r\t]*"
+                }
+
+                TOKEN {
+                    X => {"x"}
+                }
+
+                RULE {
+                    S =>
+                        | S0(A, X)
+
+                    A =>
+                        | A0(X)
+                        | A1()
+                }
+
+                START {
+                    S
+                }
+
+                ALGORITHM {
+                    LL1 Lazy
+                }
+            );
+        }
+
+        #[allow(dead_code)]
+        mod nullable {
+            use crate::*;
+
+            syntax!(
+                WHITESPACE {
+                    r"[ \n\r\t]*"
+                }
+
+                TOKEN {
+                    X => {"x"}
+                    Y => {"y"}
+                }
+
+                RULE {
+                    S =>
+                        | S0(A, X)
+
+                    A =>
+                        | A0(B)
+                        | A1()
+
+                    B =>
+                        | B0(Y)
+                        | B1()
+                }
+
+                START {
+                    S
+                }
+
+                ALGORITHM {
+                    LL1 Lazy
+                }
+            );
+        }
+
+        // xはA0のFIRSTにもAのFOLLOWにもある
+        assert_eq!(
+            first_follow::Parser::parse("x"),
+            Err(error::ParseError::Grammar(
+                "FIRST/FOLLOW conflict on X: A -> . X (2, A0) and A -> . (3, A1)".to_string()
+            ))
+        );
+
+        // A0もA1も空列を導出する．FOLLOWの記号の数によらず一度だけ報告する
+        assert_eq!(
+            nullable::Parser::parse("x"),
+            Err(error::ParseError::Grammar(
+                "FIRST/FIRST conflict on the empty string: A -> . B (2, A0) and A -> . (3, A1)"
+                    .to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_ll_left_recursion() {
+        use crate::*;
+
+        #[allow(dead_code)]
+        mod ll {
+            use crate::*;
+
+            syntax!(
+                WHITESPACE {
+                    r"[ \n\r\t]*"
+                }
+
+                TOKEN {
+                    P => {r"\+"}
+                    M => {r"\*"}
+                    N => {"[1-9][0-9]*"}
+                }
+
+                RULE {
+                    E =>
+                        | E0(E, P, T)
+                        | E1(T)
+
+                    T =>
+                        | T0(T, M, N)
+                        | T1(N)
+                }
+
+                START {
+                    E
+                }
+
+                ALGORITHM {
+                    LL
+                }
+            );
+        }
+
+        syntax!(
+            WHITESPACE {
+                r"[ \n\r\t]*"
+            }
+
+            TOKEN {
+                P => {r"\+"}
+                M => {r"\*"}
+                N => {"[1-9][0-9]*"}
+            }
+
+            RULE {
+                E =>
+                    | E0(E, P, T)
+                    | E1(T)
+
+                T =>
+                    | T0(T, M, N)
+                    | T1(N)
+            }
+
+            START {
+                E
+            }
+
+            ALGORITHM {
+                Packrat
+            }
+        );
+
+        let s = "1*2+3*4*5";
+        let t_1m2 = T::T0(
+            Box::new(T::T1(Box::new(N::new("1")))),
+            Box::new(M::new("*")),
+            Box::new(N::new("2")),
+        );
+        let t_3m4 = T::T0(
+            Box::new(T::T1(Box::new(N::new("3")))),
+            Box::new(M::new("*")),
+            Box::new(N::new("4")),
+        );
+        let t_3m4m5 = T::T0(
+            Box::new(t_3m4),
+            Box::new(M::new("*")),
+            Box::new(N::new("5")),
+        );
+        let expected = E::E0(
+            Box::new(E::E1(Box::new(t_1m2))),
+            Box::new(P::new("+")),
+            Box::new(t_3m4m5),
+        );
+
+        assert_eq!(Parser::parse(s).unwrap(), expected);
+        assert_eq!(
+            format!("{}", ll::Parser::parse(s).unwrap()),
+            "1 * 2 + 3 * 4 * 5"
+        );
+        assert!(ll::Parser::parse("1+").is_err());
+    }
+
+    #[test]
+    fn test_precedence() {
+        use crate::*;
+
+        macro_rules! arith {
+            ( $alg:ident ) => {
+                syntax!(
+                    WHITESPACE {
+                        r"[ \n\r\t]*"
+                    }
+
+                    TOKEN {
+                        Lt => {"<"}
+                        P => {r"\+"}
+                        Mi => {"-"}
+                        M => {r"\*"}
+                        Pow => {r"\^"}
+                        N => {"[1-9][0-9]*"}
+                    }
+
+                    PRECEDENCE {
+                        nonassoc Lt;
+                        left P Mi;
+                        left M;
+                        right Pow;
+                        right U;
+                    }
+
+                    RULE {
+                        E =>
+                            | Less(E, Lt, E)
+                            | Add(E, P, E)
+                            | Sub(E, Mi, E)
+                            | Mul(E, M, E)
+                            | Exp(E, Pow, E)
+                            | Neg(Mi, E) %prec U
+                            | Num(N)
+                    }
+
+                    START {
+                        E
+                    }
+
+                    ALGORITHM {
+                        $alg
+                    }
+                );
+            };
+        }
+
+        #[allow(dead_code)]
+        mod slr {
+            use crate::*;
+            arith!(SLR);
+        }
+
+        #[allow(dead_code)]
+        mod lr1 {
+            use crate::*;
+            arith!(LR1);
+        }
+
+        arith!(LALR);
+
+        let s = "-1-2*3^4^5+6";
+        let result = Parser::parse(s).unwrap();
+        let num = |s: &str| Box::new(E::Num(Box::new(N::new(s))));
+        let e_neg1 = E::Neg(Box::new(Mi::new("-")), num("1"));
+        let e_4p5 = E::Exp(num("4"), Box::new(Pow::new("^")), num("5"));
+        let e_3p4p5 = E::Exp(num("3"), Box::new(Pow::new("^")), Box::new(e_4p5));
+        let e_2m3p4p5 = E::Mul(num("2"), Box::new(M::new("*")), Box::new(e_3p4p5));
+        let e_sub = E::Sub(
+            Box::new(e_neg1),
+            Box::new(Mi::new("-")),
+            Box::new(e_2m3p4p5),
+        );
+        let expected = E::Add(Box::new(e_sub), Box::new(P::new("+")), num("6"));
+
+        assert_eq!(result, expected);
+        assert_eq!(
+            format!("{:?}", slr::Parser::parse(s).unwrap()),
+            format!("{:?}", expected)
+        );
+        assert_eq!(
+            format!("{:?}", lr1::Parser::parse(s).unwrap()),
+            format!("{:?}", expected)
+        );
+
+        let e_2p3 = E::Add(num("2"), Box::new(P::new("+")), num("3"));
+        let expected = E::Less(num("1"), Box::new(Lt::new("<")), Box::new(e_2p3));
+
+        assert_eq!(Parser::parse("1<2+3").unwrap(), expected);
+        assert!(Parser::parse("1<2<3").is_err());
+        assert!(lr1::Parser::parse("1<2<3").is_err());
+    }
+
+    #[test]
+    fn test_expect() {
+        use crate::*;
+
+        #[allow(dead_code)]
+        mod unexpected {
+            use crate::*;
+
+            syntax!(
+                WHITESPACE {
+                    r"[ \n\r\t]*"
+                }
+
+                TOKEN {
+                    I => {"if"}
+                    El => {"else"}
+                    X => {"x"}
+                }
+
+                RULE {
+                    S =>
+                        | If(I, S)
+                        | IfElse(I, S, El, S)
+                        | Other(X)
+                }
+
+                START {
+                    S
+                }
+
+                ALGORITHM {
+                    LR1 Lazy
+                }
+            );
+        }
+
+        syntax!(
+            WHITESPACE {
+                r"[ \n\r\t]*"
+            }
+
+            TOKEN {
+                I => {"if"}
+                El => {"else"}
+                X => {"x"}
+            }
+
+            EXPECT {
+                ShiftReduce 1;
+            }
+
+            RULE {
+                S =>
+                    | If(I, S)
+                    | IfElse(I, S, El, S)
+                    | Other(X)
+            }
+
+            START {
+                S
+            }
+
+            ALGORITHM {
+                LR1
+            }
+        );
+
+        let s = "if if x else x";
+        let result = Parser::parse(s).unwrap();
+        let expected = S::If(
+            Box::new(I::new("if")),
+            Box::new(S::IfElse(
+                Box::new(I::new("if")),
+                Box::new(S::Other(Box::new(X::new("x")))),
+                Box::new(El::new("else")),
+                Box::new(S::Other(Box::new(X::new("x")))),
+            )),
+        );
+
+        assert_eq!(result, expected);
+
+        let message = unexpected::Parser::parse(s).unwrap_err().to_string();
+        assert!(message.starts_with(
+            "1 shift/reduce conflicts (expected 0) and 0 reduce/reduce conflicts (expected 0)"
+        ));
+        assert!(message.contains("shift/reduce conflict in state"));
+        assert!(message.contains(" on El:"));
+        assert!(message.contains("S -> I S . El S (2, IfElse)"));
+        assert!(message.contains("S -> I S . (1, If)"));
+        assert!(message.contains("example: I I S • El S"));
+        assert!(message.contains("derivation: S -> [ I S -> [ I S • El S ] ]"));
+        assert!(message.contains("derivation: S -> [ I S -> [ I S • ] El S ]"));
+    }
+
+    #[test]
+    fn test_validate() {
+        macro_rules! invalid {
+            ( $alg:ident ) => {
+                syntax!(
+                    WHITESPACE {
+                        r"[ \n\r\t]*"
+                    }
+
+                    TOKEN {
+                        X => {"x"}
+                    }
+
+                    RULE {
+                        S =>
+                            | Unit(T)
+                            | Other(X)
+                            | Loop(A)
+
+                        T =>
+                            | Back(S)
+
+                        A =>
+                            | More(A, X)
+                    }
+
+                    START {
+                        S
+                    }
+
+                    ALGORITHM {
+                        $alg Lazy
+                    }
+                );
+            };
+        }
+
+        #[allow(dead_code)]
+        mod lalr {
+            use crate::*;
+            invalid!(LALR);
+        }
+
+        #[allow(dead_code)]
+        mod ll {
+            use crate::*;
+            invalid!(LL);
+        }
+
+        #[allow(dead_code)]
+        mod packrat {
+            use crate::*;
+            invalid!(Packrat);
+        }
+
+        let expected = "The nonterminal derives no string of tokens! (A)\n\
+                        The nonterminal derives itself! (S => T => S)";
+        assert_eq!(lalr::Parser::parse("x").unwrap_err().to_string(), expected);
+        assert_eq!(ll::Parser::parse("x").unwrap_err().to_string(), expected);
+        assert_eq!(
+            packrat::Parser::parse("x").unwrap_err().to_string(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_ebnf() {
+        macro_rules! calls {
+            ( $alg:ident ) => {
+                syntax!(
+                    WHITESPACE {
+                        r"[ \n\r\t]*"
+                    }
+
+                    TOKEN {
+                        Id => {"[a-z]+"}
+                        LP => {r"\("}
+                        RP => {r"\)"}
+                        Comma => {","}
+                        Semi => {";"}
+                        Bang => {"!"}
+                    }
+
+                    RULE {
+                        Prog =>
+                            | Stmts(Vec<Stmt>)
+
+                        Stmt =>
+                            | Call(Id, LP, Sep<Arg, Comma>, RP, Option<Bang>, Semi)
+
+                        Arg =>
+                            | Words(Vec1<Id>)
+                    }
+
+                    START {
+                        Prog
+                    }
+
+                    ALGORITHM {
+                        $alg
+                    }
+                );
+            };
+        }
+
+        #[allow(dead_code)]
+        mod ll {
+            use crate::*;
+            calls!(LL);
+        }
+
+        #[allow(dead_code)]
+        mod ll1 {
+            use crate::*;
+            calls!(LL1);
+        }
+
+        #[allow(dead_code)]
+        mod earley {
+            use crate::*;
+            calls!(Earley);
+        }
+
+        // 区切りのCommaは値に残らないので，Comma::newは使われない
+        #[allow(dead_code)]
+        mod lalr {
+            use crate::*;
+            calls!(LALR);
+        }
+
+        use lalr::*;
+
+        let s = "f(a b, c)!; g();";
+        let result = Parser::parse(s).unwrap();
+        let words = |v: &[&str]| Arg::Words(v.iter().map(|x| Id::new(x)).collect());
+        let expected = Prog::Stmts(vec![
+            Stmt::Call(
+                Box::new(Id::new("f")),
+                Box::new(LP::new("(")),
+                vec![words(&["a", "b"]), words(&["c"])],
+                Box::new(RP::new(")")),
+                Some(Box::new(Bang::new("!"))),
+                Box::new(Semi::new(";")),
+            ),
+            Stmt::Call(
+                Box::new(Id::new("g")),
+                Box::new(LP::new("(")),
+                vec![],
+                Box::new(RP::new(")")),
+                None,
+                Box::new(Semi::new(";")),
+            ),
+        ]);
+
+        assert_eq!(result, expected);
+        assert_eq!(format!("{}", result), "f ( a b c ) ! ; g ( ) ;".to_string());
+
+        for result in [
+            format!("{:?}", ll::Parser::parse(s).unwrap()),
+            format!("{:?}", ll1::Parser::parse(s).unwrap()),
+            format!("{:?}", earley::Parser::parse(s).unwrap()),
+        ] {
+            assert_eq!(result, format!("{:?}", expected));
+        }
+
+        assert_eq!(Parser::parse("").unwrap(), Prog::Stmts(vec![]));
+        assert!(Parser::parse("f(a,);").is_err());
+        assert!(ll::Parser::parse("f(,);").is_err());
+    }
+
+    #[test]
+    fn test_action() {
+        // 意味動作の$Nはmacro_rulesの中では書けないので，文法ごとに書く
+        #[allow(dead_code)]
+        mod lalr {
+            use crate::*;
+
+            syntax!(
+                WHITESPACE {
+                    r"[ \n\r\t]*"
+                }
+
+                TOKEN {
+                    N => {r"[0-9]+"}
+                    P => {r"\+"}
+                    Mi => {r"-"}
+                    M => {r"\*"}
+                    L => {r"\("}
+                    R => {r"\)"}
+                }
+
+                PRECEDENCE {
+                    left P Mi;
+                    left M;
+                    right U;
+                }
+
+                RULE {
+                    E: i64 =>
+                        | Add(E, P, E) => { $0 + $2 }
+                        | Sub(E, Mi, E) => { $0 - $2 }
+                        | Mul(E, M, E) => { $0 * $2 }
+                        | Neg(Mi, E) %prec U => { -$1 }
+                        | Paren(L, E, R) => { $1 }
+                        | Num(N) => { $0.0.parse().unwrap() }
+                }
+
+                START {
+                    E
+                }
+
+                ALGORITHM {
+                    LALR
+                }
+            );
+        }
+
+        // 型を宣言した非終端記号を木の欄にも使う
+        #[allow(dead_code)]
+        mod ll {
+            use crate::*;
+
+            syntax!(
+                WHITESPACE {
+                    r"[ \n\r\t]*"
+                }
+
+                TOKEN {
+                    N => {r"[0-9]+"}
+                    P => {r"\+"}
+                    Mi => {r"-"}
+                    Semi => {";"}
+                }
+
+                RULE {
+                    Prog =>
+                        | Stmts(Vec<Stmt>)
+
+                    Stmt =>
+                        | Print(E, Semi)
+
+                    E: i64 =>
+                        | Sum(T, Vec<Tail>) => { $0 + $1.iter().sum::<i64>() }
+
+                    Tail: i64 =>
+                        | Plus(P, T) => { $1 }
+                        | Minus(Mi, T) => { -$1 }
+
+                    T: i64 =>
+                        | Num(N) => { $0.0.parse().unwrap() }
+                }
+
+                START {
+                    Prog
+                }
+
+                ALGORITHM {
+                    LL
+                }
+            );
+        }
+
+        #[allow(dead_code)]
+        mod ll1 {
+            use crate::*;
+
+            syntax!(
+                WHITESPACE {
+                    r"[ \n\r\t]*"
+                }
+
+                TOKEN {
+                    N => {r"[0-9]+"}
+                    P => {r"\+"}
+                    Mi => {r"-"}
+                    Semi => {";"}
+                }
+
+                RULE {
+                    Prog =>
+                        | Stmts(Vec<Stmt>)
+
+                    Stmt =>
+                        | Print(E, Semi)
+
+                    E: i64 =>
+                        | Sum(T, Vec<Tail>) => { $0 + $1.iter().sum::<i64>() }
+
+                    Tail: i64 =>
+                        | Plus(P, T) => { $1 }
+                        | Minus(Mi, T) => { -$1 }
+
+                    T: i64 =>
+                        | Num(N) => { $0.0.parse().unwrap() }
+                }
+
+                START {
+                    Prog
+                }
+
+                ALGORITHM {
+                    LL1
+                }
+            );
+        }
+
+        assert_eq!(lalr::Parser::parse("1 + 2 * 3").unwrap(), 7);
+        assert_eq!(lalr::Parser::parse("(1 + 2) * 3").unwrap(), 9);
+        assert_eq!(lalr::Parser::parse("-2 * 3 - 4 - 5").unwrap(), -15);
+        assert!(lalr::Parser::parse("1 +").is_err());
+
+        let s = "1 + 2 - 4; 10;";
+        let expected = ll::Prog::Stmts(vec![
+            ll::Stmt::Print(Box::new(-1), Box::new(ll::Semi::new(";"))),
+            ll::Stmt::Print(Box::new(10), Box::new(ll::Semi::new(";"))),
+        ]);
+        let result = ll::Parser::parse(s).unwrap();
+        assert_eq!(result, expected);
+        assert_eq!(format!("{}", result), "-1 ; 10 ;".to_string());
+        assert_eq!(
+            format!("{:?}", ll1::Parser::parse(s).unwrap()),
+            format!("{:?}", expected)
+        );
+        assert!(ll1::Parser::parse("1 + ;").is_err());
+    }
+
+    #[test]
+    fn test_token_value() {
+        use crate::*;
+
+        fn unquote(s: &str) -> Result<String, String> {
+            Ok(s[1..s.len() - 1].to_string())
+        }
+
+        syntax!(
+            WHITESPACE {
+                r"[ \n\r\t]*"
+            }
+
+            TOKEN {
+                Num => {r"[0-9]+", u8}
+                Bool => {r"true|false", bool}
+                Str => {r#""[^"]*""#, String, unquote}
+                Comma => {","}
+            }
+
+            RULE {
+                L =>
+                    | Items(Sep<Item, Comma>)
+
+                Item =>
+                    | Int(Num)
+                    | Flag(Bool)
+                    | Text(Str)
+            }
+
+            START {
+                L
+            }
+
+            ALGORITHM {
+                LALR
+            }
+        );
+
+        let s = r#"12, true, "a b", 007"#;
+        let result = Parser::parse(s).unwrap();
+        let expected = L::Items(vec![
+            Item::Int(Box::new(Num::new("12"))),
+            Item::Flag(Box::new(Bool::new("true"))),
+            Item::Text(Box::new(Str::new(r#""a b""#))),
+            Item::Int(Box::new(Num::new("007"))),
+        ]);
+
+        assert_eq!(result, expected);
+        assert_eq!(Num::new("007").0, 7);
+        assert_eq!(Str::new(r#""a b""#).0, "a b".to_string());
+        assert_eq!(format!("{}", result), r#"12 true "a b" 007"#.to_string());
+        assert_eq!(
+            Parser::parse("1, 256").unwrap_err().to_string(),
+            "TokenizeError at Line 1, Col 4: \"256\" cannot be converted to Num! (number too large to fit in target type)"
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_span() {
+        macro_rules! calls {
+            ( $alg:ident ) => {
+                syntax!(
+                    WHITESPACE {
+                        r"[ \n\r\t]*"
+                    }
+
+                    TOKEN {
+                        Id => {"[a-zあ-ん]+"}
+                        LP => {r"\("}
+                        RP => {r"\)"}
+                        Semi => {";"}
+                    }
+
+                    RULE {
+                        Prog =>
+                            | Stmts(Vec<Stmt>)
+
+                        Stmt =>
+                            | Call(Id, LP, Option<Id>, RP, Semi)
+                            | Empty(Semi)
+                    }
+
+                    START {
+                        Prog
+                    }
+
+                    ALGORITHM {
+                        $alg
+                    }
+                );
+            };
+        }
+
+        mod ll {
+            use crate::*;
+            calls!(LL);
+        }
+
+        mod glr {
+            use crate::*;
+            calls!(GLR);
+        }
+
+        use crate::span::{Span, Spanned};
+        use glr::*;
+
+        let s = "f();\n  あ(x) ;\n";
+        let result = Parser::parse(s).unwrap();
+        let expected = Prog::Stmts(vec![
+            Stmt::Call(
+                Box::new(Id::new("f")),
+                Box::new(LP::new("(")),
+                None,
+                Box::new(RP::new(")")),
+                Box::new(Semi::new(";")),
+            ),
+            Stmt::Call(
+                Box::new(Id::new("あ")),
+                Box::new(LP::new("(")),
+                Some(Box::new(Id::new("x"))),
+                Box::new(RP::new(")")),
+                Box::new(Semi::new(";")),
+            ),
+        ]);
+
+        // 位置は等価性にもDebugの表示にも影響しない
+        assert_eq!(result, expected);
+        assert_eq!(format!("{:?}", result), format!("{:?}", expected));
+        assert_eq!(expected.span(), Some(Span::default()));
+
+        let Prog::Stmts(stmts) = &result;
+        let Stmt::Call(id, _, arg, _, semi) = &stmts[1] else {
+            panic!()
+        };
+        assert_eq!(id.span(), Some(Span::new(7, 10)));
+        assert_eq!(arg.as_ref().unwrap().source_str(s), Some("x"));
+        assert_eq!(semi.span().unwrap().line_col(s), (2, 8));
+        assert_eq!(stmts[1].source_str(s), Some("あ(x) ;"));
+        assert_eq!(stmts[1].span().unwrap().line_col(s), (2, 3));
+        assert_eq!(result.span(), Some(Span::new(0, 15)));
+        assert_eq!(
+            format!("{:?}", ll::Parser::parse(s).unwrap().span()),
+            format!("{:?}", result.span())
+        );
+
+        assert_eq!(Parser::parse("").unwrap().span(), None);
+        assert_eq!(
+            Parser::parse("f(); ;").unwrap().source_str("f(); ;"),
+            Some("f(); ;")
+        );
+    }
+
+    #[test]
+    fn test_error_position() {
+        macro_rules! list {
+            ( $alg:ident ) => {
+                syntax!(
+                    WHITESPACE {
+                        r"[ \n\r\t]*"
+                    }
+
+                    TOKEN {
+                        Id "identifier" => {"[a-z]+"}
+                        LP "'('" => {r"\("}
+                        RP "')'" => {r"\)"}
+                        Comma "','" => {","}
+                    }
+
+                    RULE {
+                        L =>
+                            | List(LP, Sep<Id, Comma>, RP)
+                    }
+
+                    START {
+                        L
+                    }
+
+                    ALGORITHM {
+                        $alg
+                    }
+                );
+            };
+        }
+
+        mod lalr {
+            use crate::*;
+            list!(LALR);
+        }
+
+        mod ll {
+            use crate::*;
+            list!(LL);
+        }
+
+        mod ll1 {
+            use crate::*;
+            list!(LL1);
+        }
+
+        mod glr {
+            use crate::*;
+            list!(GLR);
+        }
+
+        mod earley {
+            use crate::*;
+            list!(Earley);
+        }
+
+        // GLRとEarleyは読めた記号を報告しない
+        type Parse = fn(&str) -> Result<(), String>;
+        let parsers: [(Parse, bool); 5] = [
+            (
+                |s| {
+                    lalr::Parser::parse(s)
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                },
+                true,
+            ),
+            (
+                |s| ll::Parser::parse(s).map(|_| ()).map_err(|e| e.to_string()),
+                true,
+            ),
+            (
+                |s| ll1::Parser::parse(s).map(|_| ()).map_err(|e| e.to_string()),
+                true,
+            ),
+            (
+                |s| glr::Parser::parse(s).map(|_| ()).map_err(|e| e.to_string()),
+                false,
+            ),
+            (
+                |s| {
+                    earley::Parser::parse(s)
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                },
+                false,
+            ),
+        ];
+
+        for (parse, reports_expected) in parsers {
+            let expected = |x: &str| {
+                if reports_expected {
+                    format!(", expected {}", x)
+                } else {
+                    String::new()
+                }
+            };
+
+            assert!(parse("(a, b)").is_ok());
+            assert_eq!(
+                parse("(a,\n  b c)").unwrap_err(),
+                format!(
+                    "ParseError at Line 2, Col 5: unexpected \"c\"{}",
+                    expected("one of: ')', ','")
+                )
+            );
+            assert_eq!(
+                parse("(a,\n").unwrap_err(),
+                format!(
+                    "ParseError at Line 2, Col 1: unexpected end of input{}",
+                    expected("identifier")
+                )
+            );
+            assert_eq!(
+                parse("(a) )").unwrap_err(),
+                format!(
+                    "ParseError at Line 1, Col 5: unexpected \")\"{}",
+                    expected("end of input")
+                )
+            );
+            // 文字の途中で切らない
+            assert_eq!(
+                parse("(ä, ☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃)").unwrap_err(),
+                "TokenizeError at Line 1, Col 2: \"ä, ☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃☃\"".to_string()
+            );
+        }
+
+        use crate::error::ParseError;
+        use crate::span::Span;
+
+        let e = lalr::Parser::parse("(a b)").unwrap_err();
+        assert_eq!(e.span(), Some(Span::new(3, 4)));
+        let ParseError::UnexpectedToken {
+            token,
+            text,
+            expected,
+            state,
+            ..
+        } = e
+        else {
+            panic!()
+        };
+        assert_eq!((token, &text[..]), ("Id", "b"));
+        assert_eq!(expected, vec!["')'", "','"]);
+        assert!(state.is_some());
+
+        assert!(matches!(
+            ll::Parser::parse("(a,"),
+            Err(ParseError::UnexpectedEof {
+                line: 1,
+                col: 4,
+                state: None,
+                ..
+            })
+        ));
+        assert!(matches!(
+            ll1::Parser::parse("(a, 1)"),
+            Err(ParseError::LexError {
+                line: 1,
+                col: 5,
+                ..
+            })
+        ));
+
+        let e: Box<dyn std::error::Error> = Box::new(glr::Parser::parse(")").unwrap_err());
+        assert_eq!(
+            e.to_string(),
+            "ParseError at Line 1, Col 1: unexpected \")\"".to_string()
+        );
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        use crate::error::ParseError;
+        use crate::span::Spanned;
+        use crate::*;
+
+        syntax!(
+            WHITESPACE {
+                r"[ \n\r\t]*"
+            }
+
+            TOKEN {
+                Id => {"[a-z]+"}
+                N => {"[0-9]+"}
+                Eq "'='" => {"="}
+                Semi "';'" => {";"}
+            }
+
+            RULE {
+                Prog =>
+                    | Stmts(Vec<Stmt>)
+
+                Stmt =>
+                    | Assign(Id, Eq, N, Semi)
+                    | Bad(error, Semi)
+            }
+
+            START {
+                Prog
+            }
+
+            ALGORITHM {
+                LALR
+            }
+        );
+
+        let s = "a = 1; b = = 2; c = 3; d 4; e = 5;";
+        let (result, errors) = Parser::parse_recover(s);
+        let Prog::Stmts(stmts) = result.unwrap();
+
+        let names: Vec<_> = stmts
+            .iter()
+            .map(|x| match x {
+                Stmt::Assign(id, ..) => id.to_string(),
+                Stmt::Bad(..) => "error".to_string(),
+            })
+            .collect();
+        assert_eq!(names, vec!["a", "error", "c", "error", "e"]);
+
+        // errorは何も表示しない
+        assert_eq!(stmts[1].to_string(), ";");
+        assert_eq!(stmts[1].source_str(s), Some("= 2;"));
+
+        let Stmt::Bad(recovered, _) = &stmts[3] else {
+            panic!()
+        };
+        assert_eq!(recovered.error, errors[1]);
+
+        // 字句と同じく，回復した誤りも位置を除いて比べる
+        let (result, _) = Parser::parse_recover("b = = 2;");
+        assert!(result.is_some());
+        assert_eq!(result, Parser::parse_recover("\nb = = 2;").0);
+
+        assert_eq!(
+            errors.iter().map(|x| x.to_string()).collect::<Vec<_>>(),
+            vec![
+                "ParseError at Line 1, Col 12: unexpected \"=\", expected N",
+                "ParseError at Line 1, Col 26: unexpected \"4\", expected '='",
+            ]
+        );
+
+        // parseは最初の誤りで止まる
+        assert_eq!(Parser::parse(s).unwrap_err(), errors[0]);
+
+        // 3字句シフトするまでは次の誤りを報告しない
+        let (result, errors) = Parser::parse_recover("a = = 1; b 2; c = 3;");
+        assert!(result.is_some());
+        assert_eq!(errors.len(), 1);
+
+        // 回復できなければ木はない
+        let (result, errors) = Parser::parse_recover("a = 1; b =");
+        assert!(result.is_none());
+        assert!(matches!(errors[..], [ParseError::UnexpectedEof { .. }]));
+
+        // 読めない文字は飛ばして続ける
+        let (result, errors) = Parser::parse_recover("a = 1; !");
+        assert!(result.is_some());
+        assert!(matches!(errors[..], [ParseError::LexError { .. }]));
+    }
+
+    #[test]
+    fn test_lex_recovery() {
+        use crate::error::ParseError;
+        use crate::*;
+
+        syntax!(
+            WHITESPACE {
+                r"[ \n\r\t]*"
+            }
+
+            TOKEN {
+                Id => {"[a-z]+"}
+                N => {"[0-9]+", u8}
+                Eq "'='" => {"="}
+                Semi "';'" => {";"}
+            }
+
+            RULE {
+                Prog =>
+                    | Stmts(Vec<Stmt>)
+
+                Stmt =>
+                    | Assign(Id, Eq, N, Semi)
+                    | Bad(error, Semi)
+            }
+
+            START {
+                Prog
+            }
+
+            ALGORITHM {
+                LALR
+            }
+        );
+
+        // 続けて読めない文字は一つの誤りにまとめる
+        let s = "a = 1;\n@@ b = 300; c ?= 3;";
+        let (v, errors) = Lex::tokenize_recover(s);
+        assert_eq!(
+            v.iter().map(|x| x.get_str()).collect::<Vec<_>>(),
+            vec!["a", "=", "1", ";", "b", "=", ";", "c", "=", "3", ";"]
+        );
+        assert_eq!(
+            errors.iter().map(|x| x.span().unwrap()).collect::<Vec<_>>(),
+            vec![
+                span::Span::new(7, 9),
+                span::Span::new(14, 17),
+                span::Span::new(21, 22),
+            ]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "TokenizeError at Line 2, Col 1: \"@@ b = 300; c ?= 3;\""
+        );
+        assert!(matches!(
+            &errors[1],
+            ParseError::LexError {
+                line: 2,
+                col: 8,
+                ..
+            }
+        ));
+
+        assert!(matches!(&v[2], Token::N(x) if *x == N::new("1")));
+
+        // tokenizeは最初の誤りで止まる
+        assert_eq!(Lex::tokenize(s).unwrap_err(), errors[0]);
+        assert_eq!(Parser::parse(s).unwrap_err(), errors[0]);
+
+        // 読み飛ばした300の直後の;は，構文の誤りとして重ねて報告しない
+        let (result, errors) = Parser::parse_recover(s);
+        let Prog::Stmts(stmts) = result.unwrap();
+        assert_eq!(stmts.len(), 3);
+        assert_eq!(
+            errors.iter().map(|x| x.to_string()).collect::<Vec<_>>(),
+            vec![
+                "TokenizeError at Line 2, Col 1: \"@@ b = 300; c ?= 3;\"",
+                "TokenizeError at Line 2, Col 8: \"300\" cannot be converted to N! (number too large to fit in target type)",
+                "TokenizeError at Line 2, Col 15: \"?= 3;\"",
+            ]
+        );
+
+        // 字句の誤りと構文の誤りを位置の順に返す
+        let (_, errors) = Parser::parse_recover("b = 300; c = 1; d 3;");
+        assert_eq!(
+            errors.iter().map(|x| x.to_string()).collect::<Vec<_>>(),
+            vec![
+                "TokenizeError at Line 1, Col 5: \"300\" cannot be converted to N! (number too large to fit in target type)",
+                "ParseError at Line 1, Col 19: unexpected \"3\", expected '='",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_longest_match() {
+        use crate::*;
+
+        syntax!(
+            WHITESPACE {
+                r"[ \n\r\t]*"
+            }
+
+            TOKEN {
+                Assign => {"="}
+                EqEq => {"=="}
+                Num => {"[0-9]+"}
+                Year => {"[0-9]{4}"} % 1
+                If => {"if", Reserved}
+                Then => {"then"} % 2
+                Id => {"[a-z]+"}
+                Name => {"[a-z]+"}
+            }
+
+            RULE {
+                Prog =>
+                    | Items(Vec<Item>)
+
+                Item =>
+                    | Assign(Assign)
+                    | EqEq(EqEq)
+                    | Num(Num)
+                    | Year(Year)
+                    | If(If)
+                    | Then(Then)
+                    | Id(Id)
+                    | Name(Name)
+            }
+
+            START {
+                Prog
+            }
+
+            ALGORITHM {
+                LALR
+            }
+        );
+
+        let symbols = |s: &str| {
+            Lex::tokenize(s)
+                .unwrap()
+                .iter()
+                .map(|x| x.symbol())
+                .collect::<Vec<_>>()
+        };
+
+        // 先に宣言した字句より長く一致する字句を選ぶ
+        assert_eq!(
+            symbols("= == ==="),
+            vec!["Assign", "EqEq", "EqEq", "Assign"]
+        );
+
+        // 同じ長さなら優先度の高い字句を，それも同じなら先に宣言した字句を選ぶ
+        assert_eq!(symbols("123 2024 20245"), vec!["Num", "Year", "Num"]);
+        assert_eq!(
+            symbols("if iff then thens x"),
+            vec!["If", "Id", "Then", "Id", "Id"]
+        );
+
+        let Prog::Items(items) = Parser::parse("if x == 1").unwrap();
+        assert_eq!(items.len(), 4);
+    }
+
+    #[test]
+    fn test_lexer_modes() {
+        use crate::span::Spanned;
+        use crate::*;
+
+        syntax!(
+            WHITESPACE {
+                r"[ \n\r\t]*",
+                Str => "",
+                Comment => "",
+            }
+
+            TOKEN {
+                Id => {"[a-z]+"}
+                Plus => {r"\+"}
+                Quote "'\"'" => {r#"""#} -> push(Str)
+                RBrace "'}'" => {r"\}"} -> pop
+                Hash => {"#"} -> switch(Comment)
+                Text <Str> => {r#"[^"$]+"#}
+                Interp "'${'" <Str> => {r"\$\{"} -> push(INITIAL)
+                EndQuote "'\"'" <Str> => {r#"""#} -> pop
+                Note <Comment> => {r"[^\n]+"}
+                Newline <Comment> => {r"\n"} -> switch(INITIAL)
+            }
+
+            RULE {
+                Prog =>
+                    | Items(Vec<Item>)
+
+                Item =>
+                    | Expr(E)
+                    | Comment(Hash, Option<Note>, Newline)
+
+                E =>
+                    | Add(E, Plus, A)
+                    | A(A)
+
+                A =>
+                    | Id(Id)
+                    | Str(Quote, Vec<Part>, EndQuote)
+
+                Part =>
+                    | Text(Text)
+                    | Interp(Interp, E, RBrace)
+            }
+
+            START {
+                Prog
+            }
+
+            ALGORITHM {
+                LALR
+            }
+        );
+
+        let symbols = |s: &str| {
+            Lex::tokenize(s)
+                .unwrap()
+                .iter()
+                .map(|x| x.symbol())
+                .collect::<Vec<_>>()
+        };
+
+        // 文字列の中では空白も文字列の一部で，${から}までは元のモードで読む
+        let s = r#"a + "x ${b + "y${c}"} z""#;
+        assert_eq!(
+            symbols(s),
+            vec![
+                "Id", "Plus", "Quote", "Text", "Interp", "Id", "Plus", "Quote", "Text", "Interp",
+                "Id", "RBrace", "EndQuote", "RBrace", "Text", "EndQuote",
+            ]
+        );
+        let Prog::Items(items) = Parser::parse(s).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].source_str(s), Some(s));
+
+        // switchはモードを入れ替える
+        assert_eq!(
+            symbols("a # b + c\nd"),
+            vec!["Id", "Hash", "Note", "Newline", "Id"]
+        );
+        let Prog::Items(items) = Parser::parse("a #\nd").unwrap();
+        assert_eq!(items.len(), 3);
+
+        // 最後のモードは戻れない
+        assert_eq!(
+            Parser::parse("a } b").unwrap_err().to_string(),
+            "TokenizeError at Line 1, Col 3: \"}\" cannot pop the last mode! (INITIAL)"
+        );
+    }
+
+    #[test]
+    fn test_skip() {
+        use crate::*;
+
+        syntax!(
+            WHITESPACE {
+                r"[ \n\r\t]*"
+            }
+
+            SKIP {
+                LineComment => {r"//[^\n]*"}
+                BlockComment => {r"/\*", r"\*/", Nested}
+            }
+
+            TOKEN {
+                Id => {"[a-z]+"}
+                Slash => {"/"}
+                Star => {r"\*"}
+            }
+
+            RULE {
+                E =>
+                    | Div(E, Slash, T)
+                    | T(T)
+
+                T =>
+                    | Mul(T, Star, F)
+                    | F(F)
+
+                F =>
+                    | Id(Id)
+            }
+
+            START {
+                E
+            }
+
+            ALGORITHM {
+                LALR
+            }
+        );
+
+        let s = "a /* x /* y */ z */ / b // c\n * c";
+        let v = Lex::tokenize(s).unwrap();
+        assert_eq!(
+            v.iter().map(|x| x.get_str()).collect::<Vec<_>>(),
+            vec!["a", "/", "b", "*", "c"]
+        );
+
+        // 読み飛ばしたものは次の字句に付く
+        let Token::Slash(slash) = &v[1] else { panic!() };
+        assert_eq!(
+            slash.trivia(),
+            &[lexer::Trivia {
+                kind: "BlockComment",
+                text: "/* x /* y */ z */".to_string(),
+                span: span::Span::new(2, 19),
+            }]
+        );
+        let Token::Star(star) = &v[3] else { panic!() };
+        assert_eq!(star.trivia()[0].kind, "LineComment");
+        assert_eq!(star.trivia()[0].text, "// c");
+
+        // 読み飛ばしたものは木の等価性に影響しない
+        assert_eq!(Parser::parse(s), Parser::parse("a / b * c"));
+
+        assert_eq!(
+            Parser::parse("a /* x /* y */ b").unwrap_err().to_string(),
+            "TokenizeError at Line 1, Col 3: \"/*\" is never closed! (BlockComment)"
+        );
+    }
 }
@@ -0,0 +1,52 @@
+// 入力中の位置．木の等価性や表示には影響しない
+
+// 入力のバイト単位の範囲 start..end
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    // 与えられた範囲を全て覆う最小の範囲．一つもなければNone
+    pub fn merge(spans: impl IntoIterator<Item = Option<Span>>) -> Option<Span> {
+        spans.into_iter().flatten().reduce(|x, y| Span {
+            start: x.start.min(y.start),
+            end: x.end.max(y.end),
+        })
+    }
+
+    // 範囲が覆う入力の文字列
+    pub fn as_str<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.start..self.end]
+    }
+
+    // 範囲の始まりの1から数えた行と列
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        line_col(source, self.start)
+    }
+}
+
+// バイト位置posの1から数えた行と列．列は文字単位で数える
+pub fn line_col(source: &str, pos: usize) -> (usize, usize) {
+    let before = &source[..pos];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+
+    (line, before[line_start..].chars().count() + 1)
+}
+
+// 終端記号と木の非終端記号は，入力のどこから読んだかを知っている
+pub trait Spanned {
+    // 節点の範囲．非終端記号は子を覆い，空列を導出するときや型を宣言した非終端記号の値しか持たないときはNone
+    fn span(&self) -> Option<Span>;
+
+    // 節点を読んだ入力の文字列
+    fn source_str<'a>(&self, source: &'a str) -> Option<&'a str> {
+        self.span().map(|x| x.as_str(source))
+    }
+}
@@ -0,0 +1,9 @@
+// 文法の誤りがコンパイルエラーになり，原因の箇所を指すことを確かめる
+//
+// 期待する出力を作り直すには TRYBUILD=overwrite cargo test --test compile_fail
+
+#[test]
+fn compile_fail() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/compile_fail/*.rs");
+}
@@ -0,0 +1,26 @@
+use ruly2::*;
+
+syntax!(
+    WHITESPACE {
+        r"[ \n\r\t]*"
+    }
+
+    TOKEN {
+        N => {"[0-9]+"}
+    }
+
+    RULE {
+        E: usize =>
+            | Num(N) => { $0.0.size() }
+    }
+
+    START {
+        E
+    }
+
+    ALGORITHM {
+        LL1
+    }
+);
+
+fn main() {}
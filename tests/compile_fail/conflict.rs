@@ -0,0 +1,30 @@
+use ruly2::*;
+
+syntax!(
+    WHITESPACE {
+        r"[ \n\r\t]*"
+    }
+
+    TOKEN {
+        I => {"if"}
+        El => {"else"}
+        X => {"x"}
+    }
+
+    RULE {
+        S =>
+            | If(I, S)
+            | IfElse(I, S, El, S)
+            | Other(X)
+    }
+
+    START {
+        S
+    }
+
+    ALGORITHM {
+        LALR
+    }
+);
+
+fn main() {}
@@ -0,0 +1,34 @@
+use ruly2::*;
+
+syntax!(
+    WHITESPACE {
+        r"[ \n\r\t]*"
+    }
+
+    TOKEN {
+        I => {"if"}
+        El => {"else"}
+        X => {"x"}
+    }
+
+    EXPECT {
+        ShiftReduce 2;
+    }
+
+    RULE {
+        S =>
+            | If(I, S)
+            | IfElse(I, S, El, S)
+            | Other(X)
+    }
+
+    START {
+        S
+    }
+
+    ALGORITHM {
+        LR1
+    }
+);
+
+fn main() {}
@@ -0,0 +1,35 @@
+use ruly2::*;
+
+syntax!(
+    WHITESPACE {
+        r"[ \n\r\t]*"
+    }
+
+    TOKEN {
+        N => {"[0-9]+"}
+        P => {r"\+"}
+        Mi => {"-"}
+    }
+
+    PRECEDENCE {
+        left P Mi;
+        right U;
+    }
+
+    RULE {
+        E =>
+            | Add(E, P, E)
+            | Neg(Mi, E) %prec U
+            | Num(N)
+    }
+
+    START {
+        E
+    }
+
+    ALGORITHM {
+        LL
+    }
+);
+
+fn main() {}
@@ -0,0 +1,38 @@
+use ruly2::*;
+
+syntax!(
+    WHITESPACE {
+        r"[ \n\r\t]*",
+        Strr => "",
+        Blank => "",
+    }
+
+    SKIP {
+        Comment <Strr> => {"#[^\n]*"}
+    }
+
+    TOKEN {
+        Id => {"[a-z]+"}
+        Quote => {r#"""#} -> push(Strr)
+        Hash => {"#"} -> switch(Strr)
+        Text <Str> => {r#"[^"]+"#}
+        EndQuote <Str> => {r#"""#} -> pop
+    }
+
+    RULE {
+        A =>
+            | Id(Id)
+            | Str(Quote, Text, EndQuote)
+            | Hash(Hash)
+    }
+
+    START {
+        A
+    }
+
+    ALGORITHM {
+        LALR
+    }
+);
+
+fn main() {}
@@ -0,0 +1,27 @@
+use ruly2::*;
+
+syntax!(
+    WHITESPACE {
+        r"[ \n\r\t]*"
+    }
+
+    TOKEN {
+        Id => {"[a-z]+"}
+        Semi => {";"}
+    }
+
+    RULE {
+        Stmt =>
+            | Assign(Id, Equal, Id, Semi)
+    }
+
+    START {
+        Stmt
+    }
+
+    ALGORITHM {
+        LALR
+    }
+);
+
+fn main() {}
@@ -0,0 +1,27 @@
+use ruly2::*;
+
+syntax!(
+    WHITESPACE {
+        r"[ \n\r\t]*"
+    }
+
+    TOKEN {
+        Id => {"[a-z]+"}
+        Semi => {";"}
+    }
+
+    RULE {
+        Stmt =>
+            | A(Id, Semii)
+    }
+
+    START {
+        Stmt
+    }
+
+    ALGORITHM {
+        Packrat
+    }
+);
+
+fn main() {}